@@ -1,9 +1,8 @@
 use crate::search;
+use crate::query;
 use std::path;
 use std::io::Write;
 use std::os::unix::fs::MetadataExt;
-use bit_field::BitField;
-use std::time::UNIX_EPOCH;
 use search::loaders::FileData;
 
 mod stats {
@@ -41,9 +40,26 @@ mod stats {
             self.avg_time = (self.n as f32 * self.avg_time + elapsed) / (self.n as f32 + 1.0);
             self.n += 1;
         }
+
+        /// A single sample measured elsewhere, for operations spread over several calls
+        fn add(&mut self, content_len: usize, elapsed: f32) {
+            self.avg_size = (self.n as f32 * self.avg_size + content_len as f32) / (self.n as f32 + 1.0);
+            self.avg_time = (self.n as f32 * self.avg_time + elapsed) / (self.n as f32 + 1.0);
+            self.n += 1;
+        }
+
+        fn merge(&mut self, other: &OperationStats) {
+            let n = self.n + other.n;
+            if n > 0 {
+                self.avg_time = (self.n as f32 * self.avg_time + other.n as f32 * other.avg_time) / n as f32;
+                self.avg_size = (self.n as f32 * self.avg_size + other.n as f32 * other.avg_size) / n as f32;
+            }
+            self.n = n;
+        }
     }
 
     #[derive(Debug)]
+    #[allow(dead_code)]
     pub struct RunStats {
         operations: HashMap<String, OperationStats>,
         operation_order: Vec<String>,
@@ -51,6 +67,8 @@ mod stats {
         content_loader: String,
         avg_length: f32,
         n: usize,
+        evaluated: usize,
+        passed: usize,
         time: u128,
         instant: Instant
     }
@@ -59,7 +77,7 @@ mod stats {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
             writeln!(f, "\t{} [t={}μs]", self.content_loader, self.time as f32 / 1000.0).unwrap();
             for (op, target) in self.operation_order.iter().zip(self.targets.iter()) {
-                let key = crate::search::scorers::create_key(&op, &target);
+                let key = crate::search::scorers::create_key(op, target);
                 match self.operations.get(&key) {
                     Some(operation) => { write!(f, "{}", operation).unwrap(); },
                     None => { writeln!(f, "\t\t{} (Never executed)", op).unwrap(); }
@@ -71,30 +89,68 @@ mod stats {
 
     impl RunStats {
         pub fn _add_length(&mut self, length: usize) {
-            self.avg_length = (self.n as f32 * self.avg_length as f32 + length as f32) / (self.n as f32 + 1.0);
+            self.avg_length = (self.n as f32 * self.avg_length + length as f32) / (self.n as f32 + 1.0);
             self.n += 1;
         }
-        pub fn _start_timer(&mut self) {
+        pub fn start_timer(&mut self) {
             self.instant = Instant::now();
         }
         pub fn stop_timer(&mut self) {
             let elapsed = self.instant.elapsed();
             self.time += elapsed.as_nanos();
         }
+        pub fn record(&mut self, filtered: bool) {
+            self.evaluated += 1;
+            if filtered {
+                self.passed += 1;
+            }
+        }
+        pub fn evaluated(&self) -> usize {
+            self.evaluated
+        }
+        pub fn passed(&self) -> usize {
+            self.passed
+        }
+        pub fn time(&self) -> u128 {
+            self.time
+        }
         pub fn start_operation(&mut self, operation: &str, content_len: usize) {
             if self.operations.contains_key(operation) {
                 self.operations.get_mut(operation).expect("this should not happen").start(content_len);
             }
             else {
-                self.operations.insert(String::from(operation), OperationStats::new(&operation));
+                self.operations.insert(String::from(operation), OperationStats::new(operation));
             }
         }
+        pub fn add_operation(&mut self, operation: &str, content_len: usize, elapsed: std::time::Duration) {
+            self.operations.entry(String::from(operation))
+                .or_insert_with(|| OperationStats::new(operation))
+                .add(content_len, elapsed.as_nanos() as f32 / 1000.0);
+        }
         pub fn stop_operation(&mut self, operation: &str) {
             if self.operations.contains_key(operation) {
                 self.operations.get_mut(operation).expect("This should not happen").stop();
             }
         }
 
+        /// Folds in the stats a worker thread collected for the same run
+        pub fn merge(&mut self, other: RunStats) {
+            for (key, operation) in other.operations.iter() {
+                self.operations.entry(key.clone())
+                    .or_insert_with(|| OperationStats::new(key))
+                    .merge(operation);
+            }
+
+            let n = self.n + other.n;
+            if n > 0 {
+                self.avg_length = (self.n as f32 * self.avg_length + other.n as f32 * other.avg_length) / n as f32;
+            }
+            self.n = n;
+            self.evaluated += other.evaluated;
+            self.passed += other.passed;
+            self.time += other.time;
+        }
+
         pub fn new(run: &crate::cli::ContentRun) -> RunStats {
             let operation_order: Vec<String> = run.scorers.iter().map(|s| s.get_name()).collect();
             let targets: Vec<String> = run.targets.clone();
@@ -106,15 +162,43 @@ mod stats {
                 content_loader: String::from(run.content_loader.get_name()),
                 avg_length: 0.0, 
                 n: 0,
+                evaluated: 0,
+                passed: 0,
                 time: 0,
                 instant: Instant::now()
             }
         }
     }
 
+    #[derive(Debug, Default)]
+    pub struct CacheStats {
+        pub loads: usize,
+        pub hits: usize,
+        pub evictions: usize,
+        pub evicted_bytes: usize
+    }
+
+    impl fmt::Display for CacheStats {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            writeln!(f, "\tcontent-cache [loads={}, hits={}, evictions={}, evicted_bytes={}]", self.loads, self.hits, self.evictions, self.evicted_bytes)
+        }
+    }
+
+    impl CacheStats {
+        pub fn merge(&mut self, other: &CacheStats) {
+            self.loads += other.loads;
+            self.hits += other.hits;
+            self.evictions += other.evictions;
+            self.evicted_bytes += other.evicted_bytes;
+        }
+    }
+
     #[derive(Debug)]
     pub struct AppStats {
-        runs: Vec<RunStats> 
+        runs: Vec<RunStats>,
+        cache: CacheStats,
+        skipped_binary: usize,
+        failed: usize
     }
 
     impl fmt::Display for AppStats {
@@ -123,31 +207,465 @@ mod stats {
             for run_stats in &self.runs {
                 writeln!(f, "{}", run_stats).unwrap();
             }
-            Ok(())
+            write!(f, "{}", self.cache).unwrap();
+            writeln!(f, "\tskipped-binary [n={}]", self.skipped_binary).unwrap();
+            writeln!(f, "\tfailed [n={}]", self.failed)
         }
     }
 
     impl AppStats {
-        pub fn new() -> AppStats {
-            AppStats { runs: vec![] }
+        pub fn new(runs: &[crate::cli::ContentRun]) -> AppStats {
+            AppStats { runs: runs.iter().map(RunStats::new).collect(), cache: CacheStats::default(), skipped_binary: 0, failed: 0 }
+        }
+
+        pub fn runs(&self) -> &[RunStats] {
+            &self.runs
+        }
+
+        pub fn runs_mut(&mut self) -> &mut [RunStats] {
+            &mut self.runs
+        }
+
+        pub fn merge(&mut self, runs: Vec<RunStats>) {
+            for (run_stats, other) in self.runs.iter_mut().zip(runs) {
+                run_stats.merge(other);
+            }
+        }
+
+        pub fn merge_cache(&mut self, cache: &CacheStats) {
+            self.cache.merge(cache);
+        }
+
+        pub fn skip_binary(&mut self) {
+            self.skipped_binary += 1;
+        }
+
+        pub fn fail(&mut self) {
+            self.failed += 1;
+        }
+
+        pub fn failed(&self) -> usize {
+            self.failed
+        }
+    }
+}
+
+mod cache {
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use crate::search::loaders::{ContentLoader, FileData, LoadError};
+
+    /// Content of the file being evaluated, keyed by loader and case, so runs sharing a
+    /// loader read each file once. Cleared between files.
+    pub struct ContentCache {
+        entries: HashMap<(String, bool), Rc<String>>,
+        size: usize,
+        capacity: usize,
+        pub stats: super::stats::CacheStats
+    }
+
+    impl ContentCache {
+        pub fn new(capacity: usize) -> ContentCache {
+            ContentCache { entries: HashMap::new(), size: 0, capacity, stats: super::stats::CacheStats::default() }
+        }
+
+        pub fn clear(&mut self) {
+            self.entries.clear();
+            self.size = 0;
+        }
+
+        pub fn contains(&self, run: &super::ContentRun) -> bool {
+            let key = run.content_loader.cache_key();
+            self.entries.contains_key(&(key.clone(), run.insensitive)) || self.entries.contains_key(&(key, false))
+        }
+
+        pub fn get(&mut self, run: &super::ContentRun, filedata: &FileData) -> Result<Rc<String>, LoadError> {
+            let key = run.content_loader.cache_key();
+
+            if let Some(content) = self.entries.get(&(key.clone(), run.insensitive)) {
+                self.stats.hits += 1;
+                return Ok(Rc::clone(content));
+            }
+
+            // Lowercased content is derived from the original rather than loaded again
+            let content = self.load(run.content_loader.as_ref(), filedata)?;
+            if !run.insensitive {
+                return Ok(content);
+            }
+
+            let content = Rc::new(content.to_ascii_lowercase());
+            self.insert((key, true), Rc::clone(&content));
+            Ok(content)
+        }
+
+        /// The loader's content as it is, loaded only if no run has loaded it yet
+        pub fn load(&mut self, loader: &dyn ContentLoader, filedata: &FileData) -> Result<Rc<String>, LoadError> {
+            let key = (loader.cache_key(), false);
+
+            if let Some(content) = self.entries.get(&key) {
+                self.stats.hits += 1;
+                return Ok(Rc::clone(content));
+            }
+
+            self.stats.loads += 1;
+            let content = Rc::new(loader.load_content(filedata)?);
+            self.insert(key, Rc::clone(&content));
+            Ok(content)
+        }
+
+        fn insert(&mut self, key: (String, bool), content: Rc<String>) {
+            if content.len() > self.capacity {
+                self.stats.evictions += 1;
+                self.stats.evicted_bytes += content.len();
+                return;
+            }
+
+            // Evict the largest bodies first, they free the most for the next load
+            while self.size + content.len() > self.capacity {
+                let largest = self.entries.iter()
+                    .max_by_key(|(_, c)| c.len())
+                    .map(|(k, _)| k.clone())
+                    .expect("cache over capacity while empty");
+                let evicted = self.entries.remove(&largest).expect("largest entry exists");
+
+                self.size -= evicted.len();
+                self.stats.evictions += 1;
+                self.stats.evicted_bytes += evicted.len();
+            }
+
+            self.size += content.len();
+            self.entries.insert(key, content);
+        }
+    }
+}
+
+mod template {
+    use crate::search::loaders::{self, ContentLoader};
+    use chrono::format::{Item, StrftimeItems};
+
+    /// A piece of a `--printf` template
+    pub enum Part {
+        Text(String),
+        /// Path as given by `--absolute`
+        Path,
+        Relative,
+        Absolute,
+        /// Modification time in a strftime format
+        Mtime(String),
+        Score,
+        /// Score of one run, numbered as in `--strats`
+        Run(usize),
+        Content(Box<dyn ContentLoader>)
+    }
+
+    /// Loaders standing in for the short placeholder names
+    fn loader_name(field: &str) -> Option<&'static str> {
+        match field {
+            "name" => Some("content-title"),
+            "ext" => Some("content-ext"),
+            "size" => Some("content-size"),
+            "mode" => Some("content-mode"),
+            "owner" => Some("content-owner"),
+            "group" => Some("content-group"),
+            "text" => Some("content-text"),
+            _ => None
+        }
+    }
+
+    fn parse_field(field: &str) -> Result<Part, String> {
+        let (name, argument) = match field.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (field, None)
+        };
+
+        Ok(match (name, argument) {
+            ("path", None) => Part::Path,
+            ("relative", None) => Part::Relative,
+            ("absolute", None) => Part::Absolute,
+            ("score", None) => Part::Score,
+            ("mtime", format) => {
+                // Without a format, the time as --content-mtime writes it
+                let format = format.unwrap_or(loaders::TIME_FORMAT);
+                if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+                    return Err(format!("invalid time format '{}'", format));
+                }
+                Part::Mtime(String::from(format))
+            },
+            ("run", Some(run)) => Part::Run(run.parse().map_err(|_| format!("invalid run '{}' in {{{}}}", run, field))?),
+            (name, None) => match loaders::parse(loader_name(name).unwrap_or(name)) {
+                Some(loader) => Part::Content(loader),
+                None => return Err(format!("unknown placeholder '{{{}}}'", field))
+            },
+            _ => return Err(format!("unknown placeholder '{{{}}}'", field))
+        })
+    }
+
+    /// Splits a template into text and placeholders. `{{` and `}}` are literal braces, and
+    /// `\n`, `\t`, `\0` and `\\` are escapes as in find's `-printf`.
+    pub fn parse(template: &str) -> Result<Vec<Part>, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                },
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err(format!("unterminated placeholder in template '{}'", template))
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(parse_field(&field)?);
+                },
+                '}' => return Err(format!("unmatched '}}' in template '{}'", template)),
+                '\\' => match chars.next() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some('0') => text.push('\0'),
+                    Some('\\') => text.push('\\'),
+                    Some(c) => {
+                        text.push('\\');
+                        text.push(c);
+                    },
+                    None => text.push('\\')
+                },
+                c => text.push(c)
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(parts)
+    }
+
+    /// The content a template prints, in the order it prints it
+    pub fn loaders(parts: &[Part]) -> Vec<&dyn ContentLoader> {
+        parts.iter().filter_map(|part| match part {
+            Part::Content(loader) => Some(loader.as_ref()),
+            _ => None
+        }).collect()
+    }
+
+    /// The highest run a template refers to
+    pub fn max_run(parts: &[Part]) -> Option<usize> {
+        parts.iter().filter_map(|part| match part {
+            Part::Run(run) => Some(*run),
+            _ => None
+        }).max()
+    }
+}
+
+mod planner {
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use super::{ContentRun, RunNode};
+
+    /// Runs measured on fewer files than this fall back to the built-in estimates
+    const MIN_SAMPLES: usize = 32;
+
+    /// Runs kept in the stats file, the least measured are dropped first
+    const MAX_RUNS: usize = 1024;
+
+    #[derive(Debug, Default, Clone, Copy)]
+    struct Sample {
+        evaluated: usize,
+        passed: usize,
+        time: u128
+    }
+
+    impl Sample {
+        fn is_reliable(&self) -> bool {
+            self.evaluated >= MIN_SAMPLES
+        }
+
+        fn cost(&self) -> f32 {
+            self.time as f32 / self.evaluated as f32
+        }
+
+        fn pass_rate(&self) -> f32 {
+            self.passed as f32 / self.evaluated as f32
+        }
+    }
+
+    /// Costs and pass rates measured by earlier searches, by run and by loader
+    #[derive(Debug, Default)]
+    pub struct History {
+        runs: HashMap<String, Sample>,
+        loaders: HashMap<String, Sample>
+    }
+
+    /// `$LSEARCH_STATS`, otherwise `lsearch/stats` in the user's cache directory. Setting it
+    /// empty turns the history off.
+    fn history_path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("LSEARCH_STATS") {
+            return if path.is_empty() { None } else { Some(PathBuf::from(path)) };
+        }
+
+        Some(crate::search::loaders::cache_dir()?.join("stats"))
+    }
+
+    fn signature(run: &ContentRun) -> String {
+        let scorers: Vec<String> = run.scorers.iter().zip(run.targets.iter())
+            .map(|(scorer, target)| crate::search::scorers::create_key_from_scorer(scorer.as_ref(), target))
+            .collect();
+        format!("{} [insensitive={}] {}", run.content_loader.cache_key(), run.insensitive, scorers.join(" "))
+    }
+
+    pub fn is_pure_filter(run: &ContentRun) -> bool {
+        !run.scorers.is_empty() && run.scorers.iter().all(|scorer| scorer.is_filter())
+    }
+
+    impl History {
+        /// An unreadable or missing stats file is an empty history
+        pub fn load() -> History {
+            let mut history = History::default();
+            let contents = match history_path().and_then(|path| fs::read_to_string(path).ok()) {
+                Some(contents) => contents,
+                None => return history
+            };
+
+            for line in contents.lines() {
+                let fields: Vec<&str> = line.splitn(5, '\t').collect();
+                if let [kind, evaluated, passed, time, key] = fields[..] {
+                    let sample = match (evaluated.parse(), passed.parse(), time.parse()) {
+                        (Ok(evaluated), Ok(passed), Ok(time)) => Sample { evaluated, passed, time },
+                        _ => continue
+                    };
+
+                    match kind {
+                        "run" => { history.runs.insert(String::from(key), sample); },
+                        "loader" => { history.loaders.insert(String::from(key), sample); },
+                        _ => {}
+                    }
+                }
+            }
+
+            history
+        }
+
+        /// Adds what this search measured to the history
+        pub fn record(&mut self, runs: &[ContentRun], run_stats: &[super::stats::RunStats]) {
+            for (run, stats) in runs.iter().zip(run_stats) {
+                for sample in [
+                    self.runs.entry(signature(run)).or_default(),
+                    self.loaders.entry(run.content_loader.cache_key()).or_default()
+                ] {
+                    sample.evaluated += stats.evaluated();
+                    sample.passed += stats.passed();
+                    sample.time += stats.time();
+                }
+            }
+        }
+
+        /// Failing to write the history only costs later searches their estimates
+        pub fn save(&self) {
+            let path = match history_path() {
+                Some(path) => path,
+                None => return
+            };
+
+            let mut runs: Vec<(&String, &Sample)> = self.runs.iter().collect();
+            runs.sort_by_key(|(_, sample)| std::cmp::Reverse(sample.evaluated));
+            runs.truncate(MAX_RUNS);
+
+            let mut lines: Vec<String> = Vec::new();
+            for (kind, samples) in [("run", runs), ("loader", self.loaders.iter().collect())] {
+                for (key, sample) in samples {
+                    if sample.evaluated > 0 && !key.contains('\n') {
+                        lines.push(format!("{}\t{}\t{}\t{}\t{}", kind, sample.evaluated, sample.passed, sample.time, key));
+                    }
+                }
+            }
+            lines.sort();
+
+            // Of searches running at once, the last to finish wins
+            let _ = crate::search::loaders::write_replacing(&path, (lines.join("\n") + "\n").as_bytes());
+        }
+
+        /// Expected nanoseconds per file and share of files passed, with where each came from
+        fn estimate(&self, run: &ContentRun) -> (f32, &'static str, f32, &'static str) {
+            let measured = self.runs.get(&signature(run)).filter(|sample| sample.is_reliable());
+
+            let (cost, cost_source) = match measured.or_else(|| self.loaders.get(&run.content_loader.cache_key()).filter(|sample| sample.is_reliable())) {
+                Some(sample) => (sample.cost(), "measured"),
+                None => (run.content_loader.cost(), "built-in")
+            };
+
+            let (pass_rate, pass_source) = match measured {
+                Some(sample) => (sample.pass_rate(), "measured"),
+                None => (run.scorers.iter().map(|scorer| scorer.pass_rate()).product(), "built-in")
+            };
+
+            (cost, cost_source, pass_rate, pass_source)
         }
+    }
+
+    /// Moves the pure filter runs of each AND group to its front, cheapest per file filtered
+    /// out first. The sum of an AND group does not depend on its order, so scores are unchanged.
+    /// The reason for every placement is pushed onto `reasons`.
+    pub fn plan(tree: RunNode, runs: &[ContentRun], history: &History, reasons: &mut Vec<String>) -> RunNode {
+        match tree {
+            RunNode::And(nodes) => {
+                let nodes = nodes.into_iter().map(|node| plan(node, runs, history, reasons));
+                let (filters, rest): (Vec<RunNode>, Vec<RunNode>) = nodes
+                    .partition(|node| matches!(node, RunNode::Run(i) if is_pure_filter(&runs[*i])));
+
+                let mut ranked: Vec<(f32, RunNode)> = filters.into_iter().map(|node| {
+                    let i = match node { RunNode::Run(i) => i, _ => unreachable!() };
+                    let (cost, cost_source, pass_rate, pass_source) = history.estimate(&runs[i]);
+                    let rank = cost / (1.0 - pass_rate).max(0.001);
+
+                    reasons.push(format!("[{}] filter: ~{:.0}ns/file ({}), passes ~{:.0}% ({}), rank {:.0}",
+                        i, cost, cost_source, pass_rate * 100.0, pass_source, rank));
+                    (rank, node)
+                }).collect();
+                ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                for node in &rest {
+                    if let RunNode::Run(i) = node {
+                        reasons.push(format!("[{}] scores every file it sees, kept after the filters", i));
+                    }
+                }
 
-        pub fn push_run(&mut self, run_stats: RunStats) {
-            self.runs.push(run_stats); 
+                RunNode::And(ranked.into_iter().map(|(_, node)| node).chain(rest).collect())
+            },
+            RunNode::Or(nodes) => RunNode::Or(nodes.into_iter().map(|node| plan(node, runs, history, reasons)).collect()),
+            RunNode::Not(node) => RunNode::Not(Box::new(plan(*node, runs, history, reasons))),
+            RunNode::Run(i) => RunNode::Run(i)
         }
     }
 }
 
-pub struct ContentRun<'a> {
+pub struct ContentRun {
     content_loader: Box<dyn search::loaders::ContentLoader>,
-    scorers: Vec<&'a dyn search::scorers::ContentScorer>,
+    scorers: Vec<Box<dyn search::scorers::ContentScorer>>,
     targets: Vec<String>,
-    insensitive: bool
+    insensitive: bool,
+    skip_binary: bool,
+    /// Another run loads the same content, so it goes through the cache to be loaded once
+    shared: bool
 }
 
-impl ContentRun<'_> {
-    fn default<'a>() -> ContentRun<'a> {
-        ContentRun { content_loader: Box::new(search::loaders::ContentTitle::new()), scorers: vec![&search::scorers::Pass{}], targets: vec![String::from("")], insensitive: true }
+impl ContentRun {
+    fn default() -> ContentRun {
+        ContentRun { content_loader: Box::new(search::loaders::ContentTitle::new()), scorers: vec![Box::new(search::scorers::Pass{})], targets: vec![String::from("")], insensitive: true, skip_binary: true, shared: false }
     }
 
     //fn _new<'a>(content_loader: Box<dyn search::loaders::ContentLoader>, insensitive: bool) -> ContentRun {
@@ -168,383 +686,1198 @@ impl ContentRun<'_> {
     }
 }
 
-struct Arg {
-    pub short: char,
-    pub long: String,
-    value: Option<String>
+/// How a run scored a file, kept with the result so it prints without loading anything again
+#[derive(Clone)]
+struct RunScore {
+    passed: bool,
+    score: f32,
+    /// Each scorer's score, `None` for those left once a filter had failed
+    scorers: Vec<Option<f32>>
 }
 
-impl Arg {
-    fn new(short: char, long: &str) -> Arg {
-        Arg{ short, long: String::from(long), value: None} 
-    }
+/// A file that passed, with its score and how each run scored it. Runs the tree didn't need,
+/// as after a failed branch of `--and`, have no score.
+struct Scored {
+    score: f32,
+    filedata: FileData,
+    runs: Vec<Option<RunScore>>,
+    /// What the `--printf` template shows of the file, `None` where it couldn't be loaded
+    contents: Vec<Option<String>>
+}
 
-    fn is(&self, other: &str) -> bool {
-        let first_char = other.chars().next().unwrap();
-        if let Some(other) = other.strip_prefix("--") {
-            self.long == other
-            //self.long == other[2..]
-        }
-        else if other.starts_with('-') {
-            self.short == first_char
-        }
-        else {
-            self.long == other || if other.len() == 1 { self.short == first_char } else { false }
-        }
-    }
+/// Boolean structure over content runs, where each leaf indexes into the run list
+#[derive(Debug)]
+enum RunNode {
+    Run(usize),
+    And(Vec<RunNode>),
+    Or(Vec<RunNode>),
+    Not(Box<RunNode>)
+}
+
+impl RunNode {
+    /// Scores a file against the tree, `None` when the file is filtered out. Each run evaluated
+    /// leaves its score in `scores`.
+    fn evaluate(&self, runs: &[ContentRun], run_stats: &mut [stats::RunStats], cache: &mut cache::ContentCache, filedata: &FileData, scores: &mut [Option<RunScore>]) -> Result<Option<f32>, search::loaders::LoadError> {
+        match self {
+            RunNode::Run(i) => {
+                run_stats[*i].start_timer();
+                let run = &runs[*i];
+                let skipped = run.skip_binary && run.content_loader.is_text() && filedata.is_binary();
+
+                // Binary files are searched as though they had no text
+                let result = match if skipped { None } else { stream_scorer(run, &mut run_stats[*i], cache, filedata) } {
+                    Some(result) => result,
+                    None if skipped => Ok(run_scorer(run, &mut run_stats[*i], "")),
+                    None => cache.get(run, filedata).map(|content| run_scorer(run, &mut run_stats[*i], &content))
+                };
+                run_stats[*i].stop_timer();
+                let run_score = result?;
+                run_stats[*i].record(run_score.passed);
+
+                let score = if run_score.passed { Some(run_score.score) } else { None };
+                scores[*i] = Some(run_score);
+                Ok(score)
+            },
+            RunNode::And(nodes) => {
+                let mut score = 0.0;
+                for node in nodes {
+                    match node.evaluate(runs, run_stats, cache, filedata, scores)? {
+                        Some(node_score) => score += node_score,
+                        None => return Ok(None)
+                    }
+                }
+                Ok(Some(score))
+            },
+            RunNode::Or(nodes) => {
+                // Every branch is evaluated so that all matching branches add to the score
+                let mut branches: Vec<f32> = Vec::new();
+                for node in nodes {
+                    branches.extend(node.evaluate(runs, run_stats, cache, filedata, scores)?);
+                }
 
-    fn set_value(&mut self, other: &str) {
-        self.value = Some(String::from(other));
+                Ok(if branches.is_empty() { None } else { Some(branches.iter().sum()) })
+            },
+            RunNode::Not(node) => Ok(match node.evaluate(runs, run_stats, cache, filedata, scores)? {
+                Some(_) => None,
+                None => Some(0.0)
+            })
+        }
     }
 
-    fn get_value(&self) -> Option<String> {
-        match &self.value {
-            Some(val) => Some(String::from(val)),
-            None => None
+    fn describe(&self) -> String {
+        match self {
+            RunNode::Run(i) => format!("[{}]", i),
+            RunNode::And(nodes) => format!("({})", nodes.iter().map(|n| n.describe()).collect::<Vec<String>>().join(" AND ")),
+            RunNode::Or(nodes) => format!("({})", nodes.iter().map(|n| n.describe()).collect::<Vec<String>>().join(" OR ")),
+            RunNode::Not(node) => format!("NOT {}", node.describe())
         }
     }
 }
 
+enum Token {
+    Run(usize),
+    And,
+    Or,
+    Not,
+    Open,
+    Close
+}
 
-fn parse_args(args: std::slice::Iter<String>) -> Vec<Arg> {
-    let arg_lookup: Vec<(char, &str)> = vec![
-        ('A', "absolute"),
-        ('E', "content-ext"),
-        ('P', "content-path"),
-        ('t', "content-text"),
-        ('T', "content-title"),
-        ('C', "content-exec"),
-        ('\0', "echo"),
-        ('\0', "help"),
-        ('a', "hidden"),
-        ('i', "insensitive"),
-        ('r', "recursive"),
-        ('\0', "score"),
-        ('\0', "stats"),
-        ('\0', "strats"),
-        ('V', "version"),
-        ('h', "has"),
-        ('H', "hasnt"),
-        ('e', "is"),
-        ('L', "less"),
-        ('l', "long"),
-        ('m', "more"),
-        ('n', "not")
-            ];
+/// Recursive descent over the tokens: NOT binds tightest, then AND (implicit between runs), then OR
+struct TokenParser<'a> {
+    tokens: &'a [Token],
+    position: usize
+}
 
-    let mut parsed_args: Vec<Arg> = vec![];
+impl TokenParser<'_> {
+    fn parse_or(&mut self) -> Result<RunNode, String> {
+        let mut nodes = vec![self.parse_and()?];
 
-    for arg in args {
-        if arg.starts_with("--") {
-            let arg = arg_lookup.iter().find(|a|a.1==&arg[2..]).unwrap();
-            parsed_args.push(Arg::new(arg.0, arg.1));
+        while let Some(Token::Or) = self.tokens.get(self.position) {
+            self.position += 1;
+            nodes.push(self.parse_and()?);
         }
-        else if arg.starts_with('-') {
-            let split: Vec<char> = arg.as_bytes().iter().skip(1)
-                .map(|b| *b as char).collect();
 
-            for sub_arg in split {
-                let arg = arg_lookup.iter().find(|a|a.0==sub_arg).unwrap();
-                parsed_args.push(Arg::new(arg.0, arg.1));
+        Ok(if nodes.len() == 1 { nodes.pop().unwrap() } else { RunNode::Or(nodes) })
+    }
+
+    fn parse_and(&mut self) -> Result<RunNode, String> {
+        let mut nodes = vec![self.parse_not()?];
+
+        loop {
+            match self.tokens.get(self.position) {
+                Some(Token::And) => {
+                    self.position += 1;
+                }
+                Some(Token::Run(_)) | Some(Token::Not) | Some(Token::Open) => {}
+                _ => break
             }
+            nodes.push(self.parse_not()?);
         }
-        else if let Some(parsed_arg) = parsed_args.last_mut() {
-                parsed_arg.set_value(arg);
+
+        Ok(if nodes.len() == 1 { nodes.pop().unwrap() } else { RunNode::And(nodes) })
+    }
+
+    fn parse_not(&mut self) -> Result<RunNode, String> {
+        match self.tokens.get(self.position) {
+            Some(Token::Not) => {
+                self.position += 1;
+                Ok(RunNode::Not(Box::new(self.parse_not()?)))
+            },
+            _ => self.parse_primary()
         }
     }
 
-    parsed_args
+    fn parse_primary(&mut self) -> Result<RunNode, String> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+
+        match token {
+            Some(Token::Run(i)) => Ok(RunNode::Run(*i)),
+            Some(Token::Open) => {
+                let node = self.parse_or()?;
+                match self.tokens.get(self.position) {
+                    Some(Token::Close) => {
+                        self.position += 1;
+                        Ok(node)
+                    },
+                    _ => Err(String::from("missing closing ')'"))
+                }
+            },
+            Some(Token::Close) => Err(String::from("unexpected ')'")),
+            Some(_) => Err(String::from("expected a content run or '(' after an operator")),
+            None => Err(String::from("query ends with an operator"))
+        }
+    }
 }
 
-fn get_content_runs<'a>(args: std::slice::Iter<Arg>, _matches: &clap::ArgMatches) -> Vec<ContentRun<'a>> {
-    let mut current_loader: Box<dyn search::loaders::ContentLoader> = Box::new(search::loaders::ContentTitle::new());
-    let mut current_run: ContentRun = ContentRun{ content_loader: current_loader, scorers: Vec::new(), targets: Vec::new(), insensitive: true};
-    let mut content_runs: Vec<ContentRun> = Vec::new();
+fn get_exec_settings(query: &query::Query) -> Result<search::loaders::ExecSettings, String> {
+    let output = match query.value_of("exec-output") {
+        Some(output) => search::loaders::ExecOutput::parse(output).ok_or_else(|| format!("invalid exec output '{}'", output))?,
+        None => search::loaders::ExecOutput::Stdout
+    };
 
-    let insensitive = false;
-    for arg in args {
-        if let Some(loader) = search::loaders::parse(&arg.long) {
-            current_loader = loader;
+    // A bare number is in seconds
+    let timeout = match query.value_of("exec-timeout") {
+        Some(timeout) => Some(timeout.parse::<f64>().ok()
+            .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+            .map(std::time::Duration::from_secs_f64)
+            .or_else(|| search::scorers::parse_duration(timeout).and_then(|duration| duration.to_std().ok()))
+            .ok_or_else(|| format!("invalid exec timeout '{}'", timeout))?),
+        None => None
+    };
 
-            if current_run.is_valid() {
-                content_runs.push(current_run);
-            }
+    let jobs = match query.value_of("exec-jobs") {
+        Some(jobs) => jobs.parse::<usize>().map_err(|_| format!("invalid exec job count '{}'", jobs))?,
+        None => 0
+    };
 
-            current_run = ContentRun{ content_loader: current_loader, scorers: Vec::new(), targets: Vec::new(), insensitive};
-            continue;
-        }
-        else if arg.is("content-exec") {
-            current_loader = Box::new(search::loaders::ContentExec::new(&arg.get_value().unwrap()));
+    let cache = query.is_present("exec-cache");
+    if cache {
+        search::loaders::trim_exec_cache();
+    }
 
-            if current_run.is_valid() {
-                content_runs.push(current_run);
-            }
+    Ok(search::loaders::ExecSettings::new(output, timeout, jobs, cache))
+}
 
-            current_run = ContentRun{ content_loader: current_loader, scorers: Vec::new(), targets: Vec::new(), insensitive};
-            continue;
-        }
-        else if arg.is("insensitive") {
-            current_run.insensitive = true;
-        }
-        else if arg.is("is") {
-            current_run.scorers.push(&search::scorers::Is{});
-        }
-        else if arg.is("not") {
-            current_run.scorers.push(&search::scorers::Not{});
-        }
-        else if arg.is("has") {
-            current_run.scorers.push(&search::scorers::Has{});
-        }
-        else if arg.is("hasnt") {
-            current_run.scorers.push(&search::scorers::Hasnt{});
-        }
-        else if arg.is("more") {
-            current_run.scorers.push(&search::scorers::More{});
-        }
+fn make_loader(name: &str, value: &Option<String>, exec: &search::loaders::ExecSettings) -> Result<Box<dyn search::loaders::ContentLoader>, String> {
+    match value {
+        Some(command) if name == "content-exec" => Ok(Box::new(search::loaders::ContentExec::new(command, exec.clone())?)),
+        Some(command) if name == "exec-filter" => Ok(Box::new(search::loaders::ContentExec::new(command, exec.with_output(search::loaders::ExecOutput::Status))?)),
+        _ => search::loaders::parse(name).ok_or_else(|| format!("unknown content type '{}'", name))
+    }
+}
 
-        if let Some(s) = arg.get_value() {
-            current_run.targets.push(s);
+fn push_run(content_runs: &mut Vec<ContentRun>, tokens: &mut Vec<Token>, mut run: ContentRun) {
+    if run.is_valid() {
+        for scorer in run.scorers.iter_mut() {
+            scorer.set_insensitive(run.insensitive);
         }
+        tokens.push(Token::Run(content_runs.len()));
+        content_runs.push(run);
+    }
+}
+
+fn get_content_runs(query: &query::Query) -> Result<(Vec<ContentRun>, RunNode), String> {
+    if query.is_present("binary") && query.is_present("text-only") {
+        return Err(String::from("--binary and --text-only can't be used together"));
+    }
+    let skip_binary = !query.is_present("binary");
+    let exec = get_exec_settings(query)?;
+
+    let mut current_run: ContentRun = ContentRun{ content_loader: Box::new(search::loaders::ContentTitle::new()), scorers: Vec::new(), targets: Vec::new(), insensitive: true, skip_binary, shared: false};
+    let mut content_runs: Vec<ContentRun> = Vec::new();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut last_loader: Option<(&str, &Option<String>)> = None;
+
+    let insensitive = false;
+    for item in &query.items {
+        let token = match item {
+            query::Item::Loader { name, value } => {
+                let loader = make_loader(name, value, &exec)?;
+                push_run(&mut content_runs, &mut tokens, current_run);
+
+                last_loader = Some((name, value));
+                current_run = ContentRun{ content_loader: loader, scorers: Vec::new(), targets: Vec::new(), insensitive, skip_binary, shared: false};
+
+                // The command's exit status is the content, and only 0 passes
+                if *name == "exec-filter" {
+                    current_run.scorers.push(Box::new(search::scorers::Is{}));
+                    current_run.targets.push(String::from("0"));
+                }
+                continue;
+            },
+            query::Item::Modifier { name } => {
+                if *name == "insensitive" {
+                    current_run.insensitive = true;
+                }
+                continue;
+            },
+            query::Item::Scorer { name, target } => {
+                // Scorers and targets are zipped together, so only push a target alongside its scorer
+                if let Some(scorer) = search::scorers::parse(name, target)? {
+                    current_run.scorers.push(scorer);
+                    current_run.targets.push(String::from(target));
+                }
+                continue;
+            },
+            query::Item::Operator(query::Operator::And) => Token::And,
+            query::Item::Operator(query::Operator::Or) => Token::Or,
+            query::Item::Operator(query::Operator::Not) => Token::Not,
+            query::Item::Operator(query::Operator::Open) => Token::Open,
+            query::Item::Operator(query::Operator::Close) => Token::Close
+        };
+
+        // Operators close the current run, and the next one keeps using the same loader
+        let insensitive = current_run.insensitive;
+        push_run(&mut content_runs, &mut tokens, current_run);
+        tokens.push(token);
+
+        let loader = match last_loader {
+            Some((name, value)) => make_loader(name, value, &exec)?,
+            None => Box::new(search::loaders::ContentTitle::new())
+        };
+        current_run = ContentRun{ content_loader: loader, scorers: Vec::new(), targets: Vec::new(), insensitive, skip_binary, shared: false};
     }
 
-    if current_run.is_valid() {
-        content_runs.push(current_run);
+    push_run(&mut content_runs, &mut tokens, current_run);
+
+    let keys: Vec<String> = content_runs.iter().map(|run| run.content_loader.cache_key()).collect();
+    for (run, key) in content_runs.iter_mut().zip(keys.iter()) {
+        run.shared = keys.iter().filter(|other| *other == key).count() > 1;
     }
 
     if content_runs.is_empty() {
         content_runs.push(ContentRun::default());
+        tokens.push(Token::Run(0));
     }
 
-    content_runs
+    let mut parser = TokenParser{ tokens: &tokens, position: 0 };
+    let tree = parser.parse_or()?;
+
+    match parser.tokens.get(parser.position) {
+        Some(Token::Close) => Err(String::from("unexpected ')'")),
+        Some(_) => Err(String::from("unexpected operator")),
+        None => Ok((content_runs, tree))
+    }
 }
 
-//For optimizing later
-//fn optimize_content_run_order(&mut Vec<ContentRun> runs) {
-//
-//}
-//
-fn summarize_runs(runs: std::slice::Iter<ContentRun>) {
-    println!("Summarizing Operational Runs:");
+fn summarize_runs(out: &mut dyn Write, runs: std::slice::Iter<ContentRun>, tree: &RunNode) -> std::io::Result<()> {
+    writeln!(out, "Summarizing Operational Runs:")?;
     let mut count: u32 = 0;
     for run in runs {
-        println!("{} [insensitive={}]", run.content_loader.get_name(), run.insensitive);
+        writeln!(out, "[{}] {} [insensitive={}]", count, run.content_loader.get_name(), run.insensitive)?;
 
         for (scorer, target) in run.scorers.iter().zip(run.targets.iter()) {
-            println!("\t{}({})", scorer.get_name(), target);
+            writeln!(out, "\t{}({})", scorer.get_name(), target)?;
         }
         count += 1;
     }
 
     if count == 0 {
-        println!("\tNo operational runs");
+        writeln!(out, "\tNo operational runs")?;
+    }
+    else {
+        writeln!(out, "Query: {}", tree.describe())?;
     }
+    Ok(())
 }
 
 struct FileTraverseSpecs {
     recursive: bool,
-    hidden: bool
+    /// Whether a symlink to a directory named on the command line is listed as the directory.
+    /// Long listings show the link itself unless the name ends in `/`, as ls does.
+    follow_operand_links: bool,
+    hidden: bool,
+    ignore: bool,
+    ignore_files: Vec<path::PathBuf>,
+    min_depth: usize,
+    max_depth: usize,
+    exclude: Vec<glob::Pattern>,
+    exclude_dirs: Vec<glob::Pattern>
+}
+
+/// Whether results are listed for people or for other programs
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Text,
+    /// One JSON array of every record
+    Json,
+    /// One JSON record per line
+    Ndjson
+}
+
+impl OutputFormat {
+    fn parse(format: &str) -> Option<OutputFormat> {
+        match format {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            "ndjson" => Some(OutputFormat::Ndjson),
+            _ => None
+        }
+    }
 }
 
 struct OutputSpecs {
     absolute: bool,
     score: bool,
-    long: bool
+    long: bool,
+    quoting: QuotingStyle,
+    /// Names are written as their bytes, unquoted
+    raw: bool,
+    /// A name per line rather than the grid
+    one_per_line: bool,
+    /// What ends each entry of a list
+    terminator: u8,
+    format: OutputFormat,
+    /// Layout of each entry from `--printf`
+    template: Option<Vec<template::Part>>,
+    /// Lines of context around matched lines, when snippets are shown
+    context: Option<usize>,
+    /// Colour is only for terminals
+    colour: bool,
+    /// Sizes in K, M, G and so on in long listings
+    human: bool
 }
 
-impl FileTraverseSpecs {
-    fn new(recursive: bool, hidden:bool) -> FileTraverseSpecs {
-        FileTraverseSpecs{ recursive, hidden}
+impl OutputSpecs {
+    /// JSON, NUL separated names and templates are read by other programs
+    fn for_programs(&self) -> bool {
+        self.format != OutputFormat::Text || self.terminator == b'\0' || self.template.is_some()
     }
 }
 
-impl OutputSpecs {
-    fn new(absolute: bool, score: bool, long: bool) -> OutputSpecs {
-        OutputSpecs{ absolute, score, long }
+fn parse_depth(matches: &query::Query, name: &str) -> Result<Option<usize>, String> {
+    match matches.value_of(name) {
+        Some(depth) => depth.parse().map(Some).map_err(|_| format!("invalid depth '{}' for --{}", depth, name)),
+        None => Ok(None)
     }
 }
 
-fn get_file_traverse_specs(matches: &clap::ArgMatches) -> FileTraverseSpecs {
-    let recursive = matches.is_present("recursive");
+fn parse_patterns(matches: &query::Query, name: &str) -> Result<Vec<glob::Pattern>, String> {
+    matches.values_of(name).into_iter()
+        .map(|p| glob::Pattern::new(p).map_err(|e| format!("invalid pattern '{}' for --{}: {}", p, name, e)))
+        .collect()
+}
+
+fn get_file_traverse_specs(matches: &query::Query) -> Result<FileTraverseSpecs, String> {
+    let min_depth = parse_depth(matches, "min-depth")?;
+    let max_depth = parse_depth(matches, "max-depth")?;
+
+    // Asking for a depth only makes sense when descending
+    let recursive = matches.is_present("recursive") || min_depth.is_some() || max_depth.is_some();
     let hidden = matches.is_present("hidden");
+    let ignore = !matches.is_present("no-ignore");
+    let ignore_files = matches.values_of("ignore-file").into_iter()
+        .map(path::PathBuf::from)
+        .collect();
 
-    FileTraverseSpecs::new(recursive, hidden)
+    Ok(FileTraverseSpecs{
+        recursive,
+        follow_operand_links: !matches.is_present("long"),
+        hidden,
+        ignore,
+        ignore_files,
+        min_depth: min_depth.unwrap_or(1).max(1),
+        max_depth: max_depth.unwrap_or(usize::MAX),
+        exclude: parse_patterns(matches, "exclude")?,
+        exclude_dirs: parse_patterns(matches, "exclude-dir")?
+    })
 }
 
-fn get_output_specs(matches: &clap::ArgMatches) -> OutputSpecs {
+fn get_output_specs(matches: &query::Query) -> Result<OutputSpecs, String> {
     let absolute = matches.is_present("absolute");
     let score = matches.is_present("score");
     let long = matches.is_present("long");
+    let quoting = match matches.value_of("quoting-style") {
+        Some(style) => QuotingStyle::parse(style).ok_or_else(|| format!("invalid quoting style '{}'", style))?,
+        None => QuotingStyle::ShellEscape
+    };
+    let format = match matches.value_of("format") {
+        Some(format) => OutputFormat::parse(format).ok_or_else(|| format!("invalid format '{}'", format))?,
+        None => OutputFormat::Text
+    };
+    let print0 = matches.is_present("print0");
+    if print0 && format != OutputFormat::Text {
+        return Err(String::from("--print0 can't be used with --format"));
+    }
+    let template = match matches.value_of("printf").or_else(|| matches.value_of("template")) {
+        Some(_) if format != OutputFormat::Text || print0 => return Err(String::from("--printf can't be used with --format or --print0")),
+        Some(template) => Some(template::parse(template)?),
+        None => None
+    };
 
-    OutputSpecs::new(absolute, score, long)
-}
-
-fn get_content(run: &ContentRun, filedata: &search::loaders::FileData) -> String {
-    let mut content = run.content_loader.load_content(&filedata);
-
-    if run.insensitive {
-        content = content.to_ascii_lowercase();
+    let context = match matches.value_of("context") {
+        Some(context) => Some(context.parse::<usize>().map_err(|_| format!("invalid context '{}'", context))?),
+        None if matches.is_present("snippets") => Some(0),
+        None => None
+    };
+    if context.is_some() && (format != OutputFormat::Text || template.is_some() || print0) {
+        return Err(String::from("--context can't be used with --format, --printf or --print0"));
     }
 
-    content
+    // Like ls, pipes get a plain name per line, and names as they are unless a style is asked for
+    let terminal = std::io::IsTerminal::is_terminal(&std::io::stdout());
+    let raw = print0 || (!terminal && matches.value_of("quoting-style").is_none());
+    let one_per_line = print0 || !terminal || matches.is_present("one-per-line");
+
+    Ok(OutputSpecs {
+        absolute,
+        score,
+        long,
+        quoting,
+        raw,
+        one_per_line,
+        terminator: if print0 { b'\0' } else { b'\n' },
+        format,
+        template,
+        context,
+        colour: terminal,
+        human: matches.is_present("human-readable")
+    })
 }
 
-fn run_scorer (run: &ContentRun, run_stats: &mut stats::RunStats, content: String) -> (bool, f32) {
+fn run_scorer (run: &ContentRun, run_stats: &mut stats::RunStats, content: &str) -> RunScore {
     let mut filtered = true;
     let mut score = 0.0;
+    let mut scorers = vec![None; run.scorers.len()];
 
-    for (scorer, target) in run.scorers.iter().zip(run.targets.iter()) {
-        let operation_key = search::scorers::create_key_from_scorer(*scorer, &target);
+    for (i, (scorer, target)) in run.scorers.iter().zip(run.targets.iter()).enumerate() {
+        let operation_key = search::scorers::create_key_from_scorer(scorer.as_ref(), target);
         let target = if run.insensitive { target.to_ascii_lowercase() } else { String::from(target) };
 
         run_stats.start_operation(&operation_key, content.len());
-        let ind_score = scorer.score(&content, &target);
+        let ind_score = scorer.score(content, &target);
         run_stats.stop_operation(&operation_key);
 
         score += ind_score; 
+        scorers[i] = Some(ind_score);
 
-        if ind_score < 1.0 {
+        if scorer.is_filter() && ind_score < 1.0 {
             filtered = false;
             break;
         }
     }
 
-    (filtered, score)
+    RunScore { passed: filtered, score, scorers }
 }
 
-use glob::glob;
-
-fn is_hidden(path: &path::Path) -> bool {
-    path.file_name().unwrap().to_str().unwrap().starts_with('.')
-}
-
-pub fn process_command(pattern: &str, args: std::slice::Iter<String>, matches: &clap::ArgMatches) -> u32 {
-    //let mut pattern = path::PathBuf::from(pattern);
-    let args = parse_args(args);
-    //let command_order = process_command_order(args);
-    let runs = get_content_runs(args.iter(), matches);
-
-    let traverse_specs = get_file_traverse_specs(matches);
-    let output_specs = get_output_specs(matches);
-    
-    //optimize_content_run_order(&mut runs);
-
-    let mut app_stats = stats::AppStats::new();
-
-    //pattern = fs::canonicalize(&pattern).unwrap();
-    if matches.is_present("echo") {
-        println!("\tls {:?}", pattern);
-        println!("\tls {:?}", pattern);
-    }
-
-    if matches.is_present("strats") {
-        summarize_runs(runs.iter());
-    }
-
-    let directories = match traverse_specs.recursive {
-        true => glob(&("**/".to_owned() + pattern)),
-        false => glob(pattern)
+/// Scores a run over its content a chunk at a time, so big files are never held whole. Reading
+/// stops once every scorer has settled, or a settled filter has failed. `None` when the loader or
+/// a scorer can't stream, or the content is shared with another run through the cache.
+fn stream_scorer(run: &ContentRun, run_stats: &mut stats::RunStats, cache: &cache::ContentCache, filedata: &FileData) -> Option<Result<RunScore, search::loaders::LoadError>> {
+    if run.shared || cache.contains(run) {
+        return None;
     }
-    .expect("Failed to glob")
-    .into_iter()
-    .collect::<Vec<Result<path::PathBuf, glob::GlobError>>>();
-
-    let mut directories: Vec<path::PathBuf> = directories.into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| !is_hidden(e) || traverse_specs.hidden)
-        .map(|e| e.canonicalize().expect("Unable to canonicalize"))
-        .collect();
 
-    directories.sort_by(|a,b|b.cmp(a));
-
-    let mut directories: Vec<(f32, FileData)> = directories.into_iter()
-        .map(|e| (0.0, FileData::new(e)))
+    let targets: Vec<String> = run.targets.iter()
+        .map(|target| if run.insensitive { target.to_ascii_lowercase() } else { String::from(target) })
         .collect();
+    let mut streams = Vec::new();
+    for (scorer, target) in run.scorers.iter().zip(targets.iter()) {
+        streams.push(scorer.stream(target)?);
+    }
+    let chunks = run.content_loader.stream(filedata)?;
+
+    let mut settled = vec![false; streams.len()];
+    let mut elapsed = vec![std::time::Duration::ZERO; streams.len()];
+    let mut content_len = 0;
+
+    'chunks: for chunk in chunks {
+        let mut chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => return Some(Err(search::loaders::LoadError::new(filedata.path(), e)))
+        };
+        if run.insensitive {
+            chunk.make_ascii_lowercase();
+        }
+        content_len += chunk.len();
 
-    let mut next_directories: Vec<(f32, FileData)>;
-
-    for run in runs {
-        let mut run_stats = stats::RunStats::new(&run);
-        next_directories = Vec::new();
+        for (i, stream) in streams.iter_mut().enumerate() {
+            if settled[i] {
+                continue;
+            }
 
-        if directories.is_empty() {
-            if let Ok(path) = std::path::Path::new(pattern).canonicalize() {
-                let filedata = search::loaders::FileData::new(path);
-                let content = get_content(&run, &filedata);
-                let (filtered, score) = run_scorer(&run, &mut run_stats, content);
+            let instant = std::time::Instant::now();
+            settled[i] = stream.feed(&chunk);
+            elapsed[i] += instant.elapsed();
 
-                if filtered {
-                    next_directories.push((score, filedata));
-                }
-            }
-            else {
-                print!("no matches found: {}", pattern);
+            if settled[i] && run.scorers[i].is_filter() && stream.finish() < 1.0 {
+                break 'chunks;
             }
         }
 
-        for (_s, filedata) in directories.into_iter() {
-            let content = get_content(&run, &filedata);
+        if settled.iter().all(|settled| *settled) {
+            break;
+        }
+    }
 
-            let (filtered, score) = run_scorer(&run, &mut run_stats, content);
+    let mut filtered = true;
+    let mut score = 0.0;
+    let mut scorers = vec![None; streams.len()];
 
-            if filtered {
-                next_directories.push((score, filedata));
-            }
-        }
+    for (i, stream) in streams.iter_mut().enumerate() {
+        let operation_key = search::scorers::create_key_from_scorer(run.scorers[i].as_ref(), &run.targets[i]);
+        let instant = std::time::Instant::now();
+        let ind_score = stream.finish();
+        run_stats.add_operation(&operation_key, content_len, elapsed[i] + instant.elapsed());
 
+        score += ind_score;
+        scorers[i] = Some(ind_score);
 
-        next_directories.sort_by(|a,b| a.0.partial_cmp(&b.0).unwrap());
-        next_directories.reverse();
-        run_stats.stop_timer();
-        directories = next_directories;
-        app_stats.push_run(run_stats);
+        if run.scorers[i].is_filter() && ind_score < 1.0 {
+            filtered = false;
+            break;
+        }
     }
 
-    let parent = match path::Path::new(&pattern).canonicalize() {
-        Ok(str_path) => str_path,
-        Err(..) => path::Path::new("./").canonicalize().expect("Unable to get local directory")
-    };
-    let parent = parent.to_str().unwrap();
+    Some(Ok(RunScore { passed: filtered, score, scorers }))
+}
 
-    print_direntries(output_specs, &parent, directories);
+fn get_dir_entry_filters(traverse_specs: &FileTraverseSpecs) -> Vec<Box<dyn search::scorers::fs::DirEntryFilter>> {
+    let mut filters: Vec<Box<dyn search::scorers::fs::DirEntryFilter>> = vec![
+        Box::new(search::scorers::fs::HiddenFilter::new(traverse_specs.hidden)),
+        Box::new(search::scorers::fs::ExcludeFilter::new(traverse_specs.exclude.clone(), traverse_specs.exclude_dirs.clone()))
+    ];
 
-    if matches.is_present("stats") {
-        print!("{}", app_stats);
+    if traverse_specs.ignore {
+        filters.push(Box::new(search::scorers::fs::IgnoreFilter::new(&traverse_specs.ignore_files)));
     }
 
-    0
+    filters
 }
 
-use users::{get_user_by_uid, get_group_by_gid};
+/// The path named on the command line made absolute, resolving only its parent directories, so
+/// a symlink named directly is still the link. `None` when nothing is there, though a broken
+/// symlink counts as being there.
+fn operand_path(operand: &path::Path) -> Option<path::PathBuf> {
+    operand.symlink_metadata().ok()?;
+
+    match (operand.parent(), operand.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() { path::Path::new(".") } else { parent };
+            Some(parent.canonicalize().ok()?.join(name))
+        },
+        // `/`, `.` and `..` have no link to keep
+        _ => operand.canonicalize().ok()
+    }
+}
+
+/// An entry found by the walk, or the reason a directory couldn't be read
+type WalkResult = Result<FileData, search::loaders::LoadError>;
+
+/// Streams the entries named by `pattern`: a single file, the contents of a directory, or the
+/// matches of a glob such as `src/*.rs`. Filtered directories are pruned without being descended
+/// into. `None` when there is nothing to walk.
+/// Whether the walk goes into `root` rather than listing it. A symlink to a directory is only
+/// followed with a trailing slash or when asked to.
+fn walks_into(root: &path::Path, pattern: &path::Path, traverse_specs: &FileTraverseSpecs) -> bool {
+    let follow = traverse_specs.follow_operand_links || pattern.as_os_str().as_encoded_bytes().ends_with(b"/");
+    if follow { root.is_dir() } else { root.symlink_metadata().is_ok_and(|meta| meta.is_dir()) }
+}
+
+fn walk(pattern: &std::ffi::OsStr, traverse_specs: &FileTraverseSpecs) -> Option<Box<dyn Iterator<Item = WalkResult>>> {
+    let pattern = path::Path::new(pattern);
+
+    // The walk starts from the literal directories in front of the first glob component
+    let components: Vec<&std::ffi::OsStr> = pattern.iter().collect();
+    let split = components.iter()
+        .position(|c| c.to_string_lossy().contains(&['*', '?', '['][..]))
+        .unwrap_or(components.len());
+    let root: path::PathBuf = components[..split].iter().collect();
+    let rest: path::PathBuf = components[split..].iter().collect();
+
+    let root = if root.as_os_str().is_empty() { path::PathBuf::from(".") } else { root };
+    let root = operand_path(&root)?;
+
+    let is_dir = walks_into(&root, pattern, traverse_specs);
+
+    let (min_depth, max_depth, glob) = if split == components.len() {
+        if !is_dir {
+            (0, 0, None)
+        }
+        else if traverse_specs.recursive {
+            (traverse_specs.min_depth, traverse_specs.max_depth, None)
+        }
+        else {
+            (1, 1, None)
+        }
+    }
+    else {
+        let glob = glob::Pattern::new(&rest.to_string_lossy()).ok()?;
+        let depth = components.len() - split;
+
+        if traverse_specs.recursive {
+            (traverse_specs.min_depth.max(depth), traverse_specs.max_depth, Some(glob))
+        }
+        else {
+            (depth, depth, Some(glob))
+        }
+    };
+
+    let filters = get_dir_entry_filters(traverse_specs);
+    let recursive = traverse_specs.recursive;
+    let options = glob::MatchOptions { require_literal_separator: true, ..glob::MatchOptions::new() };
+
+    // A directory named through a symlink is listed, anything else is the entry itself
+    let entries = walkdir::WalkDir::new(&root)
+        .follow_root_links(is_dir)
+        .min_depth(min_depth)
+        .max_depth(max_depth)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(move |e| e.depth() == 0 || filters.iter().all(|f| f.filter(e)))
+        .filter(move |e| match (e, &glob) {
+            (Err(..), _) => true,
+            // Recursive globs match names at any depth, others match the path below the root
+            (Ok(e), Some(glob)) if recursive => glob.matches(&e.file_name().to_string_lossy()),
+            (Ok(e), Some(glob)) => match e.path().strip_prefix(&root) {
+                Ok(relative) => glob.matches_with(&relative.to_string_lossy(), options),
+                Err(..) => false
+            },
+            (Ok(..), None) => true
+        })
+        .map(|e| match e {
+            Ok(e) => Ok(FileData::new(e.into_path())),
+            Err(e) => {
+                let path = e.path().map(|path| path.to_path_buf()).unwrap_or_default();
+                Err(match e.into_io_error() {
+                    Some(io_error) => search::loaders::LoadError::new(&path, io_error),
+                    None => search::loaders::LoadError::new(&path, "filesystem loop")
+                })
+            }
+        });
+
+    Some(Box::new(entries))
+}
+
+fn get_jobs(matches: &query::Query) -> Result<usize, String> {
+    match matches.value_of("jobs") {
+        Some(jobs) => match jobs.parse::<usize>() {
+            Ok(0) => Ok(std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)),
+            Ok(jobs) => Ok(jobs),
+            Err(..) => Err(format!("invalid job count '{}'", jobs))
+        },
+        None => Ok(1)
+    }
+}
+
+/// Default memory cap of the per-file content cache
+const CACHE_SIZE: usize = 64 * 1024 * 1024;
+
+fn get_cache_size(matches: &query::Query) -> Result<usize, String> {
+    match matches.value_of("cache-size") {
+        Some(size) => search::scorers::parse_number(size)
+            .map(|size| size as usize)
+            .ok_or_else(|| format!("invalid cache size '{}'", size)),
+        None => Ok(CACHE_SIZE)
+    }
+}
+
+struct EvaluationSpecs {
+    jobs: usize,
+    cache_size: usize,
+    text_only: bool,
+    quiet_errors: bool,
+    strict: bool
+}
+
+fn get_evaluation_specs(matches: &query::Query) -> Result<EvaluationSpecs, String> {
+    if matches.is_present("quiet-errors") && matches.is_present("strict") {
+        return Err(String::from("--quiet-errors and --strict can't be used together"));
+    }
+
+    Ok(EvaluationSpecs {
+        jobs: get_jobs(matches)?,
+        cache_size: get_cache_size(matches)?,
+        text_only: matches.is_present("text-only"),
+        quiet_errors: matches.is_present("quiet-errors"),
+        strict: matches.is_present("strict")
+    })
+}
+
+/// A passing file's score, each run's part in it, and the content to print with it
+type Evaluation = (f32, Vec<Option<RunScore>>, Vec<Option<String>>);
+
+/// Scores a file against the tree. With `text_only`, binary files are dropped before any run
+/// sees them. The content in `shown` is loaded for files that pass, sharing what the runs loaded.
+fn evaluate_file(filedata: &FileData, runs: &[ContentRun], tree: &RunNode, run_stats: &mut [stats::RunStats], cache: &mut cache::ContentCache, text_only: bool, shown: &[&dyn search::loaders::ContentLoader]) -> Result<Option<Evaluation>, search::loaders::LoadError> {
+    if text_only && filedata.is_binary() {
+        return Ok(None);
+    }
+
+    cache.clear();
+    let mut scores = vec![None; runs.len()];
+    let score = match tree.evaluate(runs, run_stats, cache, filedata, &mut scores)? {
+        Some(score) => score,
+        None => return Ok(None)
+    };
+
+    let contents = shown.iter().map(|loader| cache.load(*loader, filedata).ok().map(|content| content.to_string())).collect();
+    Ok(Some((score, scores, contents)))
+}
+
+/// Counts a file that couldn't be searched and warns about it. In strict mode the error
+/// is handed back instead, to end the search.
+fn report_error(error: search::loaders::LoadError, app_stats: &mut stats::AppStats, specs: &EvaluationSpecs) -> Result<(), search::loaders::LoadError> {
+    app_stats.fail();
+
+    if specs.strict {
+        return Err(error);
+    }
+    if !specs.quiet_errors {
+        eprintln!("lsearch: {}", error);
+    }
+    Ok(())
+}
+
+/// Scores by index into the batch, along with the stats a worker collected
+type WorkerResult = (Vec<(usize, Result<Evaluation, search::loaders::LoadError>)>, Vec<stats::RunStats>, stats::CacheStats);
+
+/// Files handed to the workers at a time, so results still stream in from large walks
+const BATCH_SIZE: usize = 4096;
+
+/// Scores every file against the tree on `jobs` threads. Files keep their walk order, so the
+/// results and warnings are the same as evaluating them one at a time. Files that fail are
+/// left out, and in strict mode the first failure ends the search.
+fn evaluate_files(files: Box<dyn Iterator<Item = WalkResult>>, runs: &[ContentRun], tree: &RunNode, shown: &[&dyn search::loaders::ContentLoader], app_stats: &mut stats::AppStats, specs: &EvaluationSpecs) -> Result<Vec<Scored>, search::loaders::LoadError> {
+    let mut results: Vec<Scored> = Vec::new();
+
+    if specs.jobs <= 1 {
+        let mut cache = cache::ContentCache::new(specs.cache_size);
+
+        for filedata in files {
+            let filedata = match filedata {
+                Ok(filedata) => filedata,
+                Err(e) => {
+                    report_error(e, app_stats, specs)?;
+                    continue;
+                }
+            };
+
+            let score = evaluate_file(&filedata, runs, tree, app_stats.runs_mut(), &mut cache, specs.text_only, shown);
+            if filedata.checked_binary() {
+                app_stats.skip_binary();
+            }
+            match score {
+                Ok(Some((score, runs, contents))) => results.push(Scored { score, filedata, runs, contents }),
+                Ok(None) => {},
+                Err(e) => report_error(e, app_stats, specs)?
+            }
+        }
+
+        app_stats.merge_cache(&cache.stats);
+        return Ok(results);
+    }
+
+    let mut files = files.peekable();
+    while files.peek().is_some() {
+        let mut batch: Vec<FileData> = Vec::new();
+        for filedata in files.by_ref().take(BATCH_SIZE) {
+            match filedata {
+                Ok(filedata) => batch.push(filedata),
+                Err(e) => report_error(e, app_stats, specs)?
+            }
+        }
+
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        let failed = std::sync::atomic::AtomicBool::new(false);
+
+        let workers: Vec<WorkerResult> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..specs.jobs).map(|_| scope.spawn(|| {
+                let mut run_stats: Vec<stats::RunStats> = runs.iter().map(stats::RunStats::new).collect();
+                let mut cache = cache::ContentCache::new(specs.cache_size);
+                let mut scores = Vec::new();
+
+                loop {
+                    let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    if i >= batch.len() || failed.load(std::sync::atomic::Ordering::Relaxed) {
+                        break;
+                    }
+
+                    match evaluate_file(&batch[i], runs, tree, &mut run_stats, &mut cache, specs.text_only, shown) {
+                        Ok(Some(score)) => scores.push((i, Ok(score))),
+                        Ok(None) => {},
+                        Err(e) => {
+                            // Other workers can stop early, the search is over anyway
+                            if specs.strict {
+                                failed.store(true, std::sync::atomic::Ordering::Relaxed);
+                            }
+                            scores.push((i, Err(e)));
+                        }
+                    }
+                }
+
+                (scores, run_stats, cache.stats)
+            })).collect();
+
+            handles.into_iter().map(|h| h.join().expect("Worker thread panicked")).collect()
+        });
+
+        let mut scores: Vec<Option<Result<Evaluation, search::loaders::LoadError>>> = batch.iter().map(|_| None).collect();
+        for (worker_scores, run_stats, cache_stats) in workers {
+            for (i, score) in worker_scores {
+                scores[i] = Some(score);
+            }
+            app_stats.merge(run_stats);
+            app_stats.merge_cache(&cache_stats);
+        }
+
+        for (filedata, score) in batch.into_iter().zip(scores) {
+            if filedata.checked_binary() {
+                app_stats.skip_binary();
+            }
+            match score {
+                Some(Ok((score, runs, contents))) => results.push(Scored { score, filedata, runs, contents }),
+                Some(Err(e)) => report_error(e, app_stats, specs)?,
+                None => {}
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// What is shared by the path operands of one command line, so that they make one listing
+/// and read and write the history once
+pub struct Session {
+    history: planner::History,
+    /// Whether any operand measured something worth keeping in the history
+    recorded: bool,
+    /// Whether a `--format json` array has been opened, and whether a record has gone in it
+    array: Option<std::cell::Cell<bool>>
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session { history: planner::History::load(), recorded: false, array: None }
+    }
+
+    /// Closes what the operands left open and saves what they measured
+    pub fn finish(self) {
+        if let Some(first) = self.array {
+            write_stdout(if first.get() { b"]\n" } else { b"\n]\n" });
+        }
+        if self.recorded {
+            self.history.save();
+        }
+    }
+
+    pub fn process_command(&mut self, pattern: &std::ffi::OsStr, matches: &query::Query) -> u32 {
+        process_command(pattern, matches, self)
+    }
+}
+
+fn process_command(pattern: &std::ffi::OsStr, matches: &query::Query, session: &mut Session) -> u32 {
+    //let mut pattern = path::PathBuf::from(pattern);
+    let (mut runs, tree) = match get_content_runs(matches) {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("lsearch: {}", e);
+            return 2;
+        }
+    };
+
+    let traverse_specs = match get_file_traverse_specs(matches) {
+        Ok(specs) => specs,
+        Err(e) => {
+            eprintln!("lsearch: {}", e);
+            return 2;
+        }
+    };
+    let output_specs = match get_output_specs(matches) {
+        Ok(specs) => specs,
+        Err(e) => {
+            eprintln!("lsearch: {}", e);
+            return 2;
+        }
+    };
+    let evaluation_specs = match get_evaluation_specs(matches) {
+        Ok(specs) => specs,
+        Err(e) => {
+            eprintln!("lsearch: {}", e);
+            return 2;
+        }
+    };
+
+    if let Some(run) = output_specs.template.as_deref().and_then(template::max_run) {
+        if run >= runs.len() {
+            eprintln!("lsearch: no run {} for {{run:{}}}, runs are numbered from 0 as in --strats", run, run);
+            return 2;
+        }
+    }
+
+    // Content the template prints is loaded during the search, through the cache like a shared run's
+    let shown = output_specs.template.as_deref().map(template::loaders).unwrap_or_default();
+    for run in runs.iter_mut() {
+        if shown.iter().any(|loader| loader.cache_key() == run.content_loader.cache_key()) {
+            run.shared = true;
+        }
+    }
+
+    // Every operand's records go in one array, so the output is a single document
+    if output_specs.format == OutputFormat::Json && session.array.is_none() {
+        write_stdout(b"[");
+        session.array = Some(std::cell::Cell::new(true));
+    }
+
+    let mut reasons = Vec::new();
+    let tree = planner::plan(tree, &runs, &session.history, &mut reasons);
+
+    let mut app_stats = stats::AppStats::new(&runs);
+
+    //pattern = fs::canonicalize(&pattern).unwrap();
+    // Output meant for other programs stays clean, so reports go to stderr with it
+    let mut report: Box<dyn Write> = if output_specs.for_programs() { Box::new(std::io::stderr()) } else { Box::new(std::io::stdout()) };
+
+    if matches.is_present("echo") {
+        let _ = writeln!(report, "\tls {:?}", pattern);
+        let _ = writeln!(report, "\tls {:?}", pattern);
+    }
+
+    if matches.is_present("strats") {
+        let _ = summarize_runs(&mut report, runs.iter(), &tree);
+        if !reasons.is_empty() {
+            let _ = writeln!(report, "Plan:");
+            for reason in &reasons {
+                let _ = writeln!(report, "\t{}", reason);
+            }
+        }
+    }
+
+    // Like ls, a path that isn't there is an error, though the rest are still listed
+    let mut missing = false;
+    let files = match walk(pattern, &traverse_specs) {
+        Some(files) => files,
+        None => {
+            eprintln!("lsearch: no matches found: {}", quote_name(pattern, output_specs.quoting));
+            missing = true;
+            Box::new(std::iter::empty())
+        }
+    };
+
+    let mut results = match evaluate_files(files, &runs, &tree, &shown, &mut app_stats, &evaluation_specs) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("lsearch: {}", e);
+            return 2;
+        }
+    };
+
+    // Only filters are planned, so a plain listing has nothing worth keeping
+    if runs.iter().any(planner::is_pure_filter) {
+        session.history.record(&runs, app_stats.runs());
+        session.recorded = true;
+    }
+
+    results.sort_by(|a,b| b.score.partial_cmp(&a.score).unwrap());
+    let directories = results;
+
+    // Paths print relative to a directory that was asked for, otherwise to the working directory
+    let parent = match operand_path(path::Path::new(&pattern)) {
+        Some(operand) if operand.is_dir() => operand,
+        _ => path::Path::new("./").canonicalize().unwrap_or_else(|_| path::PathBuf::from("."))
+    };
+
+    // Like ls, a long listing of what is in a directory starts with its total, unlike a file
+    // named on its own. Globs list what is in the directory before them.
+    let directory = !missing && operand_path(path::Path::new(&pattern))
+        .is_none_or(|operand| walks_into(&operand, path::Path::new(&pattern), &traverse_specs));
+
+    print_direntries(output_specs, &parent, &runs, directories, directory, session);
+
+    if matches.is_present("stats") {
+        let _ = write!(report, "{}", app_stats);
+    }
+
+    // Some files couldn't be searched, so the results may be incomplete
+    if missing { 2 } else if app_stats.failed() > 0 { 1 } else { 0 }
+}
+
+use users::{get_user_by_uid, get_group_by_gid};
 use chrono::prelude::*;
 
-fn print_direntries(output_specs: OutputSpecs, parent: &str, directories: Vec<(f32, FileData)>) {
-    if output_specs.long || output_specs.score{
-        linear_print(output_specs, parent, directories);
+/// How names that aren't plain printable text are written, after GNU ls `--quoting-style`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QuotingStyle {
+    /// As they are, with anything unprintable shown as `?`
+    Literal,
+    /// Backslash escapes, including spaces
+    Escape,
+    /// Double quoted with backslash escapes
+    C,
+    /// Single quoted when the shell would need it, unprintable bytes as `$'\ooo'`
+    ShellEscape
+}
+
+impl QuotingStyle {
+    fn parse(style: &str) -> Option<QuotingStyle> {
+        match style {
+            "literal" => Some(QuotingStyle::Literal),
+            "escape" => Some(QuotingStyle::Escape),
+            "c" => Some(QuotingStyle::C),
+            "shell-escape" => Some(QuotingStyle::ShellEscape),
+            _ => None
+        }
+    }
+}
+
+/// A character of a name, or a byte that isn't part of any valid UTF-8 character
+enum NameUnit {
+    Char(char),
+    Byte(u8)
+}
+
+fn name_units(name: &std::ffi::OsStr) -> Vec<NameUnit> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut units = Vec::new();
+    for chunk in name.as_bytes().utf8_chunks() {
+        units.extend(chunk.valid().chars().map(NameUnit::Char));
+        units.extend(chunk.invalid().iter().map(|b| NameUnit::Byte(*b)));
+    }
+    units
+}
+
+/// The C escape for a unit that can't be printed as is
+fn c_escape(unit: &NameUnit) -> Option<String> {
+    let escape = match unit {
+        NameUnit::Char('\\') => "\\\\",
+        NameUnit::Char('\n') => "\\n",
+        NameUnit::Char('\t') => "\\t",
+        NameUnit::Char('\r') => "\\r",
+        NameUnit::Char('\x07') => "\\a",
+        NameUnit::Char('\x08') => "\\b",
+        NameUnit::Char('\x0b') => "\\v",
+        NameUnit::Char('\x0c') => "\\f",
+        NameUnit::Char(c) if c.is_control() => {
+            let mut bytes = [0; 4];
+            return Some(c.encode_utf8(&mut bytes).bytes().map(|b| format!("\\{:03o}", b)).collect());
+        },
+        NameUnit::Char(..) => return None,
+        NameUnit::Byte(b) => return Some(format!("\\{:03o}", b))
+    };
+    Some(String::from(escape))
+}
+
+fn quote_name(name: &std::ffi::OsStr, style: QuotingStyle) -> String {
+    let units = name_units(name);
+    if units.is_empty() {
+        return String::new();
+    }
+
+    match style {
+        QuotingStyle::Literal => units.iter()
+            .map(|unit| match unit {
+                NameUnit::Char(c) if !c.is_control() => *c,
+                _ => '?'
+            })
+            .collect(),
+        QuotingStyle::Escape => units.iter()
+            .map(|unit| match unit {
+                NameUnit::Char(' ') => String::from("\\ "),
+                NameUnit::Char(c) => c_escape(unit).unwrap_or_else(|| c.to_string()),
+                NameUnit::Byte(..) => c_escape(unit).unwrap_or_default()
+            })
+            .collect(),
+        QuotingStyle::C => {
+            let escaped: String = units.iter()
+                .map(|unit| match unit {
+                    NameUnit::Char('"') => String::from("\\\""),
+                    NameUnit::Char(c) => c_escape(unit).unwrap_or_else(|| c.to_string()),
+                    NameUnit::Byte(..) => c_escape(unit).unwrap_or_default()
+                })
+                .collect();
+            format!("\"{}\"", escaped)
+        },
+        QuotingStyle::ShellEscape => shell_escape(&units)
+    }
+}
+
+fn shell_escape(units: &[NameUnit]) -> String {
+    let is_safe = |unit: &NameUnit| matches!(unit, NameUnit::Char(c) if c.is_alphanumeric() || "_-.,/+:@%=^".contains(*c));
+    if units.iter().all(is_safe) {
+        return units.iter().filter_map(|unit| match unit { NameUnit::Char(c) => Some(*c), _ => None }).collect();
+    }
+
+    // Printable runs go in single quotes and the rest in $'...', e.g. 'a b'$'\377'
+    let mut quoted = String::new();
+    let mut open: Option<bool> = None;
+    for unit in units {
+        let escape = match unit {
+            NameUnit::Char('\\') => None,
+            _ => c_escape(unit)
+        };
+        let dollar = escape.is_some();
+
+        if open != Some(dollar) {
+            if open.is_some() {
+                quoted.push('\'');
+            }
+            quoted += if dollar { "$'" } else { "'" };
+            open = Some(dollar);
+        }
+
+        match (escape, unit) {
+            (Some(escape), _) => quoted += &escape,
+            (None, NameUnit::Char('\'')) => quoted += "'\\''",
+            (None, NameUnit::Char(c)) => quoted.push(*c),
+            (None, NameUnit::Byte(..)) => {}
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+fn print_direntries(output_specs: OutputSpecs, parent: &path::Path, runs: &[ContentRun], directories: Vec<Scored>, directory: bool, session: &Session) {
+    if output_specs.format != OutputFormat::Text || output_specs.template.is_some() || output_specs.context.is_some() || output_specs.long || output_specs.score || output_specs.one_per_line {
+        linear_print(output_specs, parent, runs, directories, directory, session);
     }
     else {
         grid_print(output_specs, parent, directories);
     }
 }
 
-fn path_abs(direntry: &FileData) -> &str {
-    direntry.path().as_os_str().to_str().unwrap()
+fn path_abs(direntry: &FileData, quoting: QuotingStyle) -> String {
+    quote_name(direntry.path().as_os_str(), quoting)
 }
-fn path_rel<'a>(direntry: &'a FileData, parent: &'a str) -> &'a str {
-    let dir_path = direntry.path().to_str().unwrap();
-    
-    if dir_path == parent {
-        return "";
-        //TODO: return parent.file_name().unwrap().to_str().unwrap();
-    }
-    match dir_path.strip_prefix(parent) {
-        Some(str) => if !str.is_empty() { &str[1..] } else { "" },
-        None => dir_path 
+/// The path below `parent`, or the whole path outside it. The parent itself, as when a
+/// symlink to a directory is listed as the link, goes by its file name.
+fn relative_path<'a>(path: &'a path::Path, parent: &path::Path) -> &'a path::Path {
+    match path.strip_prefix(parent) {
+        Ok(relative) if relative.as_os_str().is_empty() => path.file_name().map(path::Path::new).unwrap_or(path),
+        Ok(relative) => relative,
+        Err(..) => path
     }
 }
 
-fn print_dir<'a>(direntry: &'a FileData, parent: &'a str, absolute: bool) -> &'a str {
+fn path_rel(direntry: &FileData, parent: &path::Path, quoting: QuotingStyle) -> String {
+    quote_name(relative_path(direntry.path(), parent).as_os_str(), quoting)
+}
+
+fn print_dir(direntry: &FileData, parent: &path::Path, absolute: bool, quoting: QuotingStyle) -> String {
     if absolute {
-        let dir_path = path_abs(&direntry);
+        let dir_path = path_abs(direntry, quoting);
 
         if direntry.path().is_dir() {
             colour::green!("{}", dir_path);
@@ -555,7 +1888,7 @@ fn print_dir<'a>(direntry: &'a FileData, parent: &'a str, absolute: bool) -> &'a
         dir_path
     }
     else {
-        let dir_path = path_rel(&direntry, parent);
+        let dir_path = path_rel(direntry, parent, quoting);
         if direntry.path().is_dir() {
             colour::green!("{}", dir_path);
         }
@@ -566,89 +1899,501 @@ fn print_dir<'a>(direntry: &'a FileData, parent: &'a str, absolute: bool) -> &'a
     }
 }
 
+/// Writes `prefix` and the entry's name, then the terminator. Raw names are written byte for
+/// byte, so they survive a trip through `xargs -0`.
+fn print_entry(prefix: &str, direntry: &FileData, parent: &path::Path, output_specs: &OutputSpecs) {
+    let mut line = prefix.as_bytes().to_vec();
+    line.extend(entry_name(direntry, parent, output_specs, output_specs.absolute));
+    line.push(output_specs.terminator);
+    write_stdout(&line);
+}
+
+/// Writes to stdout in one piece. A closed pipe, as with `| head`, ends the listing quietly
+/// rather than panicking as `print!` would.
+fn write_stdout(bytes: &[u8]) {
+    let _ = std::io::stdout().lock().write_all(bytes);
+}
+
+/// The entry's path as it is printed: quoted, or its bytes when names are raw
+fn entry_name(direntry: &FileData, parent: &path::Path, output_specs: &OutputSpecs, absolute: bool) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = direntry.path();
+    match (output_specs.raw, absolute) {
+        (true, true) => path.as_os_str().as_bytes().to_vec(),
+        (true, false) => relative_path(path, parent).as_os_str().as_bytes().to_vec(),
+        (false, true) => path_abs(direntry, output_specs.quoting).into_bytes(),
+        (false, false) => path_rel(direntry, parent, output_specs.quoting).into_bytes()
+    }
+}
+
 trait PrintlnFormatter {
-    fn print(&self, score: &f32, parent: &str, direntry: &FileData, output_specs: &OutputSpecs);
+    /// Printed before the first entry
+    fn begin(&self) {}
+    fn print(&self, result: &Scored, parent: &path::Path, output_specs: &OutputSpecs);
+    /// Printed after the last entry
+    fn end(&self) {}
 }
 
 struct ScoreFormatter { }
 impl PrintlnFormatter for ScoreFormatter {
-    fn print(&self, score: &f32, parent: &str, direntry: &FileData, output_specs: &OutputSpecs) {
+    fn print(&self, result: &Scored, parent: &path::Path, output_specs: &OutputSpecs) {
         if output_specs.absolute {
-            let dir_path = path_abs(&direntry);
-            println!("[{}]{}", score, dir_path);
+            print_entry(&format!("[{}]", result.score), &result.filedata, parent, output_specs);
         }
         else {
-            let clean_path = path_rel(&direntry, parent); 
-            println!("[{}] {}", score, clean_path);
+            print_entry(&format!("[{}] ", result.score), &result.filedata, parent, output_specs);
+        }
+    }
+}
+
+/// Sizes as `ls -h` writes them: powers of 1024, rounded up, with a decimal below 10
+fn human_size(size: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+
+    if size < 1024 {
+        return size.to_string();
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    // Rounding up can carry into the next unit, e.g. 1023.5K is 1.0M
+    let rounded = if value < 10.0 { (value * 10.0).ceil() / 10.0 } else { value.ceil() };
+    if rounded >= 1024.0 && unit < UNITS.len() - 1 {
+        format!("1.0{}", UNITS[unit + 1])
+    }
+    else if rounded < 10.0 {
+        format!("{:.1}{}", rounded, UNITS[unit])
+    }
+    else {
+        format!("{:.0}{}", rounded, UNITS[unit])
+    }
+}
+
+/// The type character and permissions, e.g. `drwxr-sr-t`. Special bits take the place of
+/// execute, in lowercase when execute is set too.
+fn mode_string(file_type: std::fs::FileType, mode: u32) -> String {
+    use std::os::unix::fs::FileTypeExt;
+
+    let kind = if file_type.is_dir() { 'd' }
+        else if file_type.is_symlink() { 'l' }
+        else if file_type.is_fifo() { 'p' }
+        else if file_type.is_socket() { 's' }
+        else if file_type.is_block_device() { 'b' }
+        else if file_type.is_char_device() { 'c' }
+        else { '-' };
+
+    let mut permissions = String::from(kind);
+    // Read, write and execute bits for owner, group and others, with the special bit of each
+    for (shift, special, set, unset) in [(6, 0o4000, 's', 'S'), (3, 0o2000, 's', 'S'), (0, 0o1000, 't', 'T')] {
+        let bits = (mode >> shift) & 0o7;
+        permissions.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        permissions.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        permissions.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-'
+        });
+    }
+    permissions
+}
+
+/// Files modified longer ago than this, or in the future, show a year rather than a time
+const RECENT_SECONDS: i64 = 31_556_952 / 2;
+
+/// The columns `ls -l` shows for an entry, before padding. Describes symlinks themselves
+/// rather than their targets, and `None` when the entry can't be described at all.
+fn long_columns(direntry: &FileData, human: bool) -> Option<[String; 6]> {
+    let meta = direntry.path().symlink_metadata().ok()?;
+
+    // Orphaned ids have no name to show, so fall back to the number
+    let owner = get_user_by_uid(meta.uid())
+        .map(|owner| owner.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| meta.uid().to_string());
+    let group = get_group_by_gid(meta.gid())
+        .map(|group| group.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| meta.gid().to_string());
+
+    // Devices have no size, so like ls show their major and minor numbers
+    let file_type = meta.file_type();
+    let size = if std::os::unix::fs::FileTypeExt::is_block_device(&file_type) || std::os::unix::fs::FileTypeExt::is_char_device(&file_type) {
+        let device = meta.rdev();
+        format!("{}, {}", ((device >> 8) & 0xfff) | ((device >> 32) & !0xfff), (device & 0xff) | ((device >> 12) & 0xffff_ff00))
+    }
+    else if human {
+        human_size(meta.len())
+    }
+    else {
+        meta.len().to_string()
+    };
+
+    let age = Local::now().timestamp() - meta.mtime();
+    let format = if (0..RECENT_SECONDS).contains(&age) { "%b %e %H:%M" } else { "%b %e  %Y" };
+    let modified = Local.timestamp_opt(meta.mtime(), 0).earliest()
+        .map(|modified| modified.format(format).to_string())
+        .unwrap_or_else(|| String::from("?"));
+
+    Some([mode_string(file_type, meta.mode()), meta.nlink().to_string(), owner, group, size, modified])
+}
+
+/// A listing like `ls -l`, with the columns aligned over every entry
+struct LongFormatter {
+    human: bool,
+    widths: [usize; 6],
+    /// Space the entries take on disk, shown first when they are what is in a directory
+    total: Option<String>
+}
+
+impl LongFormatter {
+    fn new(directories: &[Scored], human: bool, directory: bool) -> LongFormatter {
+        let mut widths = [1; 6];
+        let mut blocks = 0;
+        for Scored { filedata: direntry, .. } in directories {
+            for (width, column) in widths.iter_mut().zip(long_columns(direntry, human).iter().flatten()) {
+                *width = (*width).max(column.chars().count());
+            }
+            blocks += direntry.path().symlink_metadata().map(|meta| meta.blocks()).unwrap_or(0);
         }
+
+        // Blocks are 512 bytes, and ls counts in kilobytes rounded up
+        let total = directory.then(|| if human { human_size(blocks * 512) } else { blocks.div_ceil(2).to_string() });
+        LongFormatter { human, widths, total }
     }
 }
 
-struct LongFormatter { }
 impl PrintlnFormatter for LongFormatter {
-    fn print(&self, _score: &f32, parent: &str, direntry: &FileData, output_specs: &OutputSpecs) {
-        let meta = direntry.metadata();
-        let mode = meta.mode();
-        let mut permission_str = String::new();
+    fn begin(&self) {
+        if let Some(total) = &self.total {
+            write_stdout(format!("total {}\n", total).as_bytes());
+        }
+    }
+
+    fn print(&self, result: &Scored, parent: &path::Path, output_specs: &OutputSpecs) {
+        use std::os::unix::ffi::OsStrExt;
+
+        let direntry = &result.filedata;
+        let w = &self.widths;
+        // Like ls, list what can't be described with its details unknown
+        let [mode, links, owner, group, size, modified] = long_columns(direntry, self.human)
+            .unwrap_or_else(|| ["??????????", "?", "?", "?", "?", "?"].map(String::from));
 
-        permission_str += if meta.is_dir() { "d" } else { "-" };
+        let mut line = format!("{:<w0$} {:>w1$} {:<w2$} {:<w3$} {:>w4$} {:<w5$} ", mode, links, owner, group, size, modified,
+            w0 = w[0], w1 = w[1], w2 = w[2], w3 = w[3], w4 = w[4], w5 = w[5]).into_bytes();
+        line.extend(entry_name(direntry, parent, output_specs, output_specs.absolute));
 
-        for i in 0..9 {
-            let bit = mode.get_bit(i);
-            if i % 3 == 0 {
-                permission_str += if bit { "-" } else { "r" };
+        if let Ok(target) = std::fs::read_link(direntry.path()) {
+            line.extend_from_slice(b" -> ");
+            if output_specs.raw {
+                line.extend_from_slice(target.as_os_str().as_bytes());
             }
-            else if (i + 1) % 3 == 0 {
-                permission_str += if bit { "-" } else { "x" };
+            else {
+                line.extend(quote_name(target.as_os_str(), output_specs.quoting).into_bytes());
             }
-            else if (i + 2) % 3 == 0 {
-                permission_str += if bit { "w" } else { "-" };
+        }
+        line.push(output_specs.terminator);
+        write_stdout(&line);
+    }
+}
+
+struct StdFormatter { }
+impl PrintlnFormatter for StdFormatter {
+    fn print(&self, result: &Scored, parent: &path::Path, output_specs: &OutputSpecs) {
+        print_entry("", &result.filedata, parent, output_specs);
+    }
+}
+
+/// A string as a JSON string literal
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped += "\\\"",
+            '\\' => escaped += "\\\\",
+            '\n' => escaped += "\\n",
+            '\r' => escaped += "\\r",
+            '\t' => escaped += "\\t",
+            c if (c as u32) < 0x20 => escaped += &format!("\\u{:04x}", c as u32),
+            c => escaped.push(c)
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// JSON has no infinities or NaN, so those are null
+fn json_number(value: f32) -> String {
+    if value.is_finite() { value.to_string() } else { String::from("null") }
+}
+
+/// Writes a record per file for other programs. Fields are only ever added, never renamed:
+/// `path`, `relative`, `score`, `type`, `size`, `mode`, `links`, `uid`, `gid`, `owner`, `group`,
+/// `mtime`, and `runs` with each run's `content`, `passed`, `score` and `scorers`. Names that
+/// aren't UTF-8 are written lossily, and metadata that can't be read is null.
+struct JsonFormatter<'a> {
+    runs: &'a [ContentRun],
+    /// The session's open array and whether it is still empty, or none for one record per line
+    array: Option<&'a std::cell::Cell<bool>>
+}
+
+impl JsonFormatter<'_> {
+    fn file_type(direntry: &FileData) -> &'static str {
+        use std::os::unix::fs::FileTypeExt;
+
+        match direntry.path().symlink_metadata().map(|meta| meta.file_type()) {
+            Ok(kind) if kind.is_symlink() => "symlink",
+            Ok(kind) if kind.is_dir() => "directory",
+            Ok(kind) if kind.is_file() => "file",
+            Ok(kind) if kind.is_fifo() => "fifo",
+            Ok(kind) if kind.is_socket() => "socket",
+            Ok(kind) if kind.is_block_device() => "block-device",
+            Ok(kind) if kind.is_char_device() => "char-device",
+            _ => "unknown"
+        }
+    }
+
+    fn metadata_fields(direntry: &FileData) -> Vec<(&'static str, String)> {
+        let null = String::from("null");
+        let meta = match direntry.metadata() {
+            Ok(meta) => meta,
+            Err(..) => return ["size", "mode", "links", "uid", "gid", "owner", "group", "mtime"].iter().map(|field| (*field, null.clone())).collect()
+        };
+
+        let owner = get_user_by_uid(meta.uid()).map(|owner| json_string(&owner.name().to_string_lossy())).unwrap_or_else(|| null.clone());
+        let group = get_group_by_gid(meta.gid()).map(|group| json_string(&group.name().to_string_lossy())).unwrap_or_else(|| null.clone());
+        let mtime = Local.timestamp_opt(meta.mtime(), meta.mtime_nsec() as u32).earliest()
+            .map(|mtime| json_string(&mtime.to_rfc3339()))
+            .unwrap_or(null);
+
+        vec![
+            ("size", meta.len().to_string()),
+            ("mode", json_string(&format!("{:o}", meta.mode() & 0o7777))),
+            ("links", meta.nlink().to_string()),
+            ("uid", meta.uid().to_string()),
+            ("gid", meta.gid().to_string()),
+            ("owner", owner),
+            ("group", group),
+            ("mtime", mtime)
+        ]
+    }
+
+    fn run_fields(&self, scores: &[Option<RunScore>]) -> String {
+        let null = String::from("null");
+
+        let runs: Vec<String> = self.runs.iter().zip(scores.iter()).map(|(run, run_score)| {
+            let scorers: Vec<String> = run.scorers.iter().zip(run.targets.iter()).enumerate()
+                .map(|(i, (scorer, target))| {
+                    let score = run_score.as_ref().and_then(|run_score| run_score.scorers[i]).map(json_number).unwrap_or_else(|| null.clone());
+                    format!("{{\"scorer\":{},\"target\":{},\"filter\":{},\"score\":{}}}",
+                        json_string(&scorer.get_name()), json_string(target), scorer.is_filter(), score)
+                })
+                .collect();
+            let (passed, score) = match run_score {
+                Some(run_score) => (run_score.passed.to_string(), json_number(run_score.score)),
+                None => (null.clone(), null.clone())
+            };
+
+            format!("{{\"content\":{},\"passed\":{},\"score\":{},\"scorers\":[{}]}}",
+                json_string(run.content_loader.get_name()), passed, score, scorers.join(","))
+        }).collect();
+
+        runs.join(",")
+    }
+}
+
+impl PrintlnFormatter for JsonFormatter<'_> {
+    fn print(&self, result: &Scored, parent: &path::Path, _output_specs: &OutputSpecs) {
+        let direntry = &result.filedata;
+        let path = direntry.path();
+        let relative = relative_path(path, parent);
+
+        let mut record = format!("{{\"path\":{},\"relative\":{},\"score\":{},\"type\":{}",
+            json_string(&path.to_string_lossy()), json_string(&relative.to_string_lossy()), json_number(result.score), json_string(Self::file_type(direntry)));
+        for (field, value) in Self::metadata_fields(direntry) {
+            record += &format!(",{}:{}", json_string(field), value);
+        }
+        record += &format!(",\"runs\":[{}]}}", self.run_fields(&result.runs));
+
+        match self.array {
+            Some(first) => write_stdout(format!("{}\n{}", if first.replace(false) { "" } else { "," }, record).as_bytes()),
+            None => write_stdout(format!("{}\n", record).as_bytes())
+        }
+    }
+}
+
+/// Lays each entry out as `--printf` asks. Values that can't be read print as `?`.
+struct TemplateFormatter<'a> {
+    parts: &'a [template::Part]
+}
+
+impl PrintlnFormatter for TemplateFormatter<'_> {
+    fn print(&self, result: &Scored, parent: &path::Path, output_specs: &OutputSpecs) {
+        let direntry = &result.filedata;
+        let mut line: Vec<u8> = Vec::new();
+        let mut contents = result.contents.iter();
+
+        for part in self.parts {
+            match part {
+                template::Part::Text(text) => line.extend_from_slice(text.as_bytes()),
+                template::Part::Path => line.extend(entry_name(direntry, parent, output_specs, output_specs.absolute)),
+                template::Part::Relative => line.extend(entry_name(direntry, parent, output_specs, false)),
+                template::Part::Absolute => line.extend(entry_name(direntry, parent, output_specs, true)),
+                template::Part::Mtime(format) => {
+                    let mtime = direntry.metadata().ok()
+                        .and_then(|meta| Local.timestamp_opt(meta.mtime(), meta.mtime_nsec() as u32).earliest())
+                        .map(|mtime| mtime.format(format).to_string());
+                    line.extend_from_slice(mtime.as_deref().unwrap_or("?").as_bytes());
+                },
+                template::Part::Score => line.extend_from_slice(result.score.to_string().as_bytes()),
+                template::Part::Run(run) => {
+                    let run_score = result.runs[*run].as_ref().map(|run_score| run_score.score.to_string());
+                    line.extend_from_slice(run_score.as_deref().unwrap_or("?").as_bytes());
+                },
+                template::Part::Content(..) => {
+                    let content = contents.next().and_then(Option::as_deref);
+                    line.extend_from_slice(content.unwrap_or("?").as_bytes());
+                }
             }
         }
+        write_stdout(&line);
+    }
+}
 
-        let dir_path = if output_specs.absolute { path_abs(&direntry) } else { path_rel(&direntry, parent) };
-        let timestamp = meta.modified().expect("Unable to retrieve modfied").duration_since(UNIX_EPOCH).expect("Uh oh").as_secs();
-        let modified = Utc.timestamp(timestamp as i64, 0);
-        let modified: DateTime<Local> = DateTime::with_timezone(&modified, &Local);
-        let owner = get_user_by_uid(meta.uid()).unwrap();
-        let owner_name = owner.name().to_str().unwrap();
-        
-        let group = get_group_by_gid(meta.gid()).unwrap();
-        let group_name = group.name().to_str().unwrap();
+/// Where a line matches any of the text runs' targets, merged and in order
+fn line_matches(line: &str, matchers: &[(&dyn search::scorers::ContentScorer, String, bool)]) -> Vec<std::ops::Range<usize>> {
+    // Lowercasing ASCII keeps every byte offset, so ranges still index the original line
+    let lowered = line.to_ascii_lowercase();
+    let mut ranges: Vec<std::ops::Range<usize>> = matchers.iter()
+        .flat_map(|(scorer, target, insensitive)| scorer.find(if *insensitive { &lowered } else { line }, target))
+        .filter(|range| !range.is_empty())
+        .collect();
+    ranges.sort_by_key(|range| range.start);
 
-        println!("{} {} {} {} {}", permission_str, owner_name, group_name, modified.format("%b %d %H:%M"), dir_path);
+    let mut merged: Vec<std::ops::Range<usize>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range)
+        }
     }
+    merged
 }
 
-struct StdFormatter { }
-impl PrintlnFormatter for StdFormatter {
-    fn print(&self, _score: &f32, parent: &str, direntry: &FileData, output_specs: &OutputSpecs) {
-        if output_specs.absolute {
-            let dir_path = path_abs(&direntry);
-            println!("{}",  dir_path);
+/// Writes a numbered line, `:` after the number for matches and `-` for context as grep does
+fn print_snippet_line(number: usize, separator: char, line: &str, ranges: &[std::ops::Range<usize>], colour: bool) {
+    if !colour {
+        write_stdout(format!("{:>6}{} {}\n", number, separator, line).as_bytes());
+        return;
+    }
+
+    print!("{:>6}{} ", number, separator);
+    let mut position = 0;
+    for range in ranges {
+        colour::prnt!("{}", &line[position..range.start]);
+        colour::red!("{}", &line[range.clone()]);
+        position = range.end;
+    }
+    colour::prnt_ln!("{}", &line[position..]);
+}
+
+/// Prints the lines under a result that its text runs' targets match, with `context` lines
+/// around each and `--` between groups that aren't next to each other. Only scorers that look
+/// for something, such as `--has` and `--matches`, pick lines out.
+fn print_snippets(direntry: &FileData, runs: &[ContentRun], context: usize, colour: bool) {
+    use std::io::BufRead;
+
+    let matchers: Vec<(&dyn search::scorers::ContentScorer, String, bool)> = runs.iter()
+        .filter(|run| run.content_loader.is_text() && !(run.skip_binary && direntry.is_binary()))
+        .flat_map(|run| run.scorers.iter().zip(run.targets.iter()).map(move |(scorer, target)| {
+            let target = if run.insensitive { target.to_ascii_lowercase() } else { String::from(target) };
+            (scorer.as_ref(), target, run.insensitive)
+        }))
+        .collect();
+    if matchers.is_empty() || !direntry.path().is_file() {
+        return;
+    }
+
+    let mut reader = match std::fs::File::open(direntry.path()) {
+        Ok(file) => std::io::BufReader::new(file),
+        Err(..) => return
+    };
+
+    let mut before: std::collections::VecDeque<(usize, String)> = std::collections::VecDeque::new();
+    let mut after = 0;
+    let mut last_printed: Option<usize> = None;
+    let mut buffer = Vec::new();
+    let mut number = 0;
+
+    loop {
+        buffer.clear();
+        match reader.read_until(b'\n', &mut buffer) {
+            Ok(0) | Err(..) => break,
+            Ok(..) => number += 1
         }
-        else {
-            let clean_path = path_rel(&direntry, parent); 
-            println!("{}", clean_path);
+        let line = String::from_utf8_lossy(&buffer);
+        let line = line.trim_end_matches(['\n', '\r']);
+
+        let ranges = line_matches(line, &matchers);
+        if !ranges.is_empty() {
+            let first = before.front().map(|(number, _)| *number).unwrap_or(number);
+            if last_printed.is_some_and(|last| first > last + 1) {
+                write_stdout(b"    --\n");
+            }
+            for (number, line) in before.drain(..) {
+                print_snippet_line(number, '-', &line, &[], colour);
+            }
+            print_snippet_line(number, ':', line, &ranges, colour);
+            last_printed = Some(number);
+            after = context;
+        }
+        else if after > 0 {
+            print_snippet_line(number, '-', line, &[], colour);
+            last_printed = Some(number);
+            after -= 1;
+        }
+        else if context > 0 {
+            before.push_back((number, String::from(line)));
+            if before.len() > context {
+                before.pop_front();
+            }
         }
     }
 }
 
-fn linear_print(output_specs: OutputSpecs, parent: &str, directories: Vec<(f32, FileData)>) {
-    let formatter: Box<dyn PrintlnFormatter> = if output_specs.score {
+/// Prints one entry per line. `directory` when the entries are what is in a directory.
+fn linear_print(output_specs: OutputSpecs, parent: &path::Path, runs: &[ContentRun], directories: Vec<Scored>, directory: bool, session: &Session) {
+    let formatter: Box<dyn PrintlnFormatter> = if let Some(parts) = &output_specs.template {
+        Box::new(TemplateFormatter { parts })
+    }
+    else if output_specs.format != OutputFormat::Text {
+        Box::new(JsonFormatter { runs, array: session.array.as_ref() })
+    }
+    else if output_specs.score {
         Box::new(ScoreFormatter {})
     }
+    else if output_specs.long {
+        Box::new(LongFormatter::new(&directories, output_specs.human, directory))
+    }
     else {
-        //Box::new(StdFormatter {})
-        Box::new(LongFormatter {})
+        Box::new(StdFormatter {})
     };
 
-    for (score, direntry) in directories {
-        formatter.print(&score, parent, &direntry, &output_specs);
+    formatter.begin();
+    for result in directories {
+        formatter.print(&result, parent, &output_specs);
+        if let Some(context) = output_specs.context {
+            print_snippets(&result.filedata, runs, context, output_specs.colour);
+        }
     }
+    formatter.end();
 }
 
-fn grid_print(output_specs: OutputSpecs, parent: &str, directories: Vec<(f32, FileData)>) {
+fn grid_print(output_specs: OutputSpecs, parent: &path::Path, directories: Vec<Scored>) {
     const MAX_LINE: u32 = 80;
 
     if directories.is_empty() {
@@ -656,33 +2401,138 @@ fn grid_print(output_specs: OutputSpecs, parent: &str, directories: Vec<(f32, Fi
     }
 
     let max_width: u32 = directories.iter()
-        .map(|x| if output_specs.absolute { path_abs(&x.1) } else { path_rel(&x.1, parent) }.len())
+        .map(|x| if output_specs.absolute { path_abs(&x.filedata, output_specs.quoting) } else { path_rel(&x.filedata, parent, output_specs.quoting) }.chars().count())
         .max()
         .unwrap() as u32 + 5;
 
     let columns = MAX_LINE / max_width;
 
     let mut x = 0;
-    for (_score, direntry) in directories {
+    for Scored { filedata: direntry, .. } in directories {
         if x > columns {
             x = 0;
             colour::white_ln!("");
         }
 
         if output_specs.absolute {
-            let dir_path = print_dir(&direntry, parent, true);
-            for _x in (dir_path.len() as u32)..max_width {
+            let dir_path = print_dir(&direntry, parent, true, output_specs.quoting);
+            for _x in (dir_path.chars().count() as u32)..max_width {
                 print!(" ")
             }
         }
         else {
-            let dir_path = print_dir(&direntry, parent, false);
-            for _x in (dir_path.len() as u32)..max_width {
+            let dir_path = print_dir(&direntry, parent, false, output_specs.quoting);
+            for _x in (dir_path.chars().count() as u32)..max_width {
                 print!(" ")
             }
         }
 
         x += 1;
     };
+    colour::white_ln!("");
     std::io::stdout().flush().expect("Failed to flush stdout");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::ffi::OsStrExt;
+
+    fn quote(name: &[u8], style: QuotingStyle) -> String {
+        quote_name(std::ffi::OsStr::from_bytes(name), style)
+    }
+
+    #[test]
+    fn literal_names_hide_what_cant_be_printed() {
+        assert_eq!(quote(b"a b", QuotingStyle::Literal), "a b");
+        assert_eq!(quote(b"a\nb\tc", QuotingStyle::Literal), "a?b?c");
+        assert_eq!(quote(b"a\xffb", QuotingStyle::Literal), "a?b");
+        assert_eq!(quote("caf\u{e9}".as_bytes(), QuotingStyle::Literal), "caf\u{e9}");
+    }
+
+    #[test]
+    fn escaped_names_use_backslashes() {
+        assert_eq!(quote(b"a b", QuotingStyle::Escape), "a\\ b");
+        assert_eq!(quote(b"a\nb", QuotingStyle::Escape), "a\\nb");
+        assert_eq!(quote(b"a\\b", QuotingStyle::Escape), "a\\\\b");
+        assert_eq!(quote(b"\xff\x01", QuotingStyle::Escape), "\\377\\001");
+        assert_eq!(quote(b"it's", QuotingStyle::Escape), "it's");
+    }
+
+    #[test]
+    fn c_names_are_double_quoted() {
+        assert_eq!(quote(b"plain", QuotingStyle::C), "\"plain\"");
+        assert_eq!(quote(b"a \"b\"", QuotingStyle::C), "\"a \\\"b\\\"\"");
+        assert_eq!(quote(b"a\tb\xff", QuotingStyle::C), "\"a\\tb\\377\"");
+    }
+
+    #[test]
+    fn shell_escaped_names_paste_back_into_a_shell() {
+        assert_eq!(quote(b"src/main.rs", QuotingStyle::ShellEscape), "src/main.rs");
+        assert_eq!(quote(b"a b", QuotingStyle::ShellEscape), "'a b'");
+        assert_eq!(quote(b"it's", QuotingStyle::ShellEscape), "'it'\\''s'");
+        assert_eq!(quote(b"a\\b", QuotingStyle::ShellEscape), "'a\\b'");
+        assert_eq!(quote(b"a\nb", QuotingStyle::ShellEscape), "'a'$'\\n''b'");
+        assert_eq!(quote(b"\xff", QuotingStyle::ShellEscape), "$'\\377'");
+        assert_eq!(quote(b"", QuotingStyle::ShellEscape), "");
+    }
+
+    #[test]
+    fn sizes_round_up_like_ls() {
+        assert_eq!(human_size(0), "0");
+        assert_eq!(human_size(1023), "1023");
+        assert_eq!(human_size(1024), "1.0K");
+        assert_eq!(human_size(1025), "1.1K");
+        assert_eq!(human_size(10 * 1024), "10K");
+        assert_eq!(human_size(10 * 1024 + 1), "11K");
+        assert_eq!(human_size(1024 * 1024 - 1), "1.0M");
+        assert_eq!(human_size(3_500_000), "3.4M");
+        assert_eq!(human_size(5 << 40), "5.0T");
+    }
+
+    #[test]
+    fn modes_show_special_bits_in_place_of_execute() {
+        let directory = std::fs::symlink_metadata(env!("CARGO_MANIFEST_DIR")).unwrap().file_type();
+        let manifest = std::fs::symlink_metadata(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).unwrap().file_type();
+
+        assert_eq!(mode_string(directory, 0o755), "drwxr-xr-x");
+        assert_eq!(mode_string(manifest, 0o644), "-rw-r--r--");
+        assert_eq!(mode_string(manifest, 0o4755), "-rwsr-xr-x");
+        assert_eq!(mode_string(manifest, 0o4644), "-rwSr--r--");
+        assert_eq!(mode_string(manifest, 0o2750), "-rwxr-s---");
+        assert_eq!(mode_string(manifest, 0o2640), "-rw-r-S---");
+        assert_eq!(mode_string(directory, 0o1777), "drwxrwxrwt");
+        assert_eq!(mode_string(directory, 0o1776), "drwxrwxrwT");
+        assert_eq!(mode_string(manifest, 0), "----------");
+    }
+
+    /// The run tree for a query, as `--strats` describes it
+    fn tree(args: &str) -> Result<String, String> {
+        let query = query::parse(args.split(' ').map(std::ffi::OsString::from)).map_err(|e| e.to_string())?;
+        get_content_runs(&query).map(|(_, tree)| tree.describe())
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and_tighter_than_or() {
+        assert_eq!(tree("-t -h a --or -t -h b -t -h c").unwrap(), "([0] OR ([1] AND [2]))");
+        assert_eq!(tree("-t -h a -t -h b --or -t -h c").unwrap(), "(([0] AND [1]) OR [2])");
+        assert_eq!(tree("--not-run -t -h a -t -h b").unwrap(), "(NOT [0] AND [1])");
+        assert_eq!(tree("-t -h a --and --not-run --not-run -t -h b").unwrap(), "([0] AND NOT NOT [1])");
+        assert_eq!(tree("--not-run -t -h a --or -t -h b").unwrap(), "(NOT [0] OR [1])");
+    }
+
+    #[test]
+    fn groups_override_precedence() {
+        assert_eq!(tree("( -t -h a --or -t -h b ) -t -h c").unwrap(), "(([0] OR [1]) AND [2])");
+        assert_eq!(tree("--not-run ( -t -h a -t -h b )").unwrap(), "NOT ([0] AND [1])");
+        assert_eq!(tree("( ( -t -h a ) )").unwrap(), "[0]");
+    }
+
+    #[test]
+    fn misplaced_operators_are_errors() {
+        assert_eq!(tree("-t -h a --or").unwrap_err(), "query ends with an operator");
+        assert_eq!(tree("( -t -h a").unwrap_err(), "missing closing ')'");
+        assert_eq!(tree("-t -h a )").unwrap_err(), "unexpected ')'");
+        assert_eq!(tree("--or -t -h a").unwrap_err(), "expected a content run or '(' after an operator");
+    }
+}
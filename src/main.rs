@@ -1,22 +1,41 @@
 use std::env;
+use std::ffi::OsStr;
 
 mod cli;
+mod query;
 mod search;
 
 fn main() {
-    let yaml = clap::load_yaml!("cli.yaml");
-    let matches = clap::App::from(yaml).get_matches();
-        
-    let result: Vec<String> = env::args().collect();
-    let patterns = matches.values_of("path");
+    let query = match query::parse(env::args_os().skip(1)) {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    if query.is_present("help") {
+        query::print_help();
+        return;
+    }
+    if query.is_present("version") {
+        query::print_version();
+        return;
+    }
 
-    if let Some(patterns) = patterns{
-        for pattern in patterns {
-            cli::process_command(pattern, result.iter(), &matches);
+    let mut session = cli::Session::new();
+    let mut code = 0;
+    if !query.paths.is_empty() {
+        for pattern in &query.paths {
+            code = code.max(session.process_command(pattern, &query));
         }
     }
     else {
-        cli::process_command("./*", result.iter(), &matches);
+        code = session.process_command(OsStr::new("./*"), &query);
+    }
+    session.finish();
+
+    if code != 0 {
+        std::process::exit(code as i32);
     }
-    println!();
 }
@@ -0,0 +1,497 @@
+use std::ffi::OsString;
+use std::fmt;
+
+const NAME: &str = "L-Search";
+const VERSION: &str = "0.0.2";
+const AUTHOR: &str = "Jackson C <jacksonc@alerik.de>";
+const ABOUT: &str = "Search through ALL files";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// Starts a new content run
+    Loader,
+    /// Filter or scorer applied to the current run
+    Scorer,
+    /// Combines runs into a boolean expression
+    Operator,
+    /// Changes the current run
+    Modifier,
+    /// Global flag, position doesn't matter
+    Switch,
+    /// Global option taking a value, may be repeated
+    Setting
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Zero,
+    One,
+    /// Either `LOW..HIGH` or two separate values
+    Range
+}
+
+#[derive(Debug)]
+pub struct Flag {
+    pub short: Option<char>,
+    pub long: &'static str,
+    pub kind: Kind,
+    pub arity: Arity,
+    pub about: &'static str
+}
+
+const fn flag(short: Option<char>, long: &'static str, kind: Kind, arity: Arity, about: &'static str) -> Flag {
+    Flag { short, long, kind, arity, about }
+}
+
+pub const FLAGS: &[Flag] = &[
+    //Content types
+    flag(Some('t'), "content-text", Kind::Loader, Arity::Zero, "File contents"),
+    flag(Some('E'), "content-ext", Kind::Loader, Arity::Zero, "File extension"),
+    flag(Some('T'), "content-title", Kind::Loader, Arity::Zero, "File title"),
+    flag(Some('P'), "content-path", Kind::Loader, Arity::Zero, "File path"),
+    flag(Some('C'), "content-exec", Kind::Loader, Arity::One, "Output of a command run on each file, e.g. \"stat -c %U {}\""),
+    flag(None, "exec-filter", Kind::Loader, Arity::One, "Keep files a command exits with 0 for, e.g. \"test -x {}\""),
+    flag(None, "content-size", Kind::Loader, Arity::Zero, "File size in bytes"),
+    flag(None, "content-owner", Kind::Loader, Arity::Zero, "File owner name"),
+    flag(None, "content-group", Kind::Loader, Arity::Zero, "File group name"),
+    flag(None, "content-mode", Kind::Loader, Arity::Zero, "File permissions in octal"),
+    flag(None, "content-mtime", Kind::Loader, Arity::Zero, "File modification time"),
+    flag(None, "content-ctime", Kind::Loader, Arity::Zero, "File status change time"),
+    flag(None, "content-atime", Kind::Loader, Arity::Zero, "File access time"),
+
+    //Filters
+    flag(Some('e'), "is", Kind::Scorer, Arity::One, "Filter where value is equal to"),
+    flag(Some('n'), "not", Kind::Scorer, Arity::One, "Filter where value is not equal to"),
+    flag(Some('h'), "has", Kind::Scorer, Arity::One, "Filter where value contains"),
+    flag(Some('H'), "hasnt", Kind::Scorer, Arity::One, "Filter where value doesn't contain"),
+    flag(None, "matches", Kind::Scorer, Arity::One, "Filter where value matches regex"),
+    flag(None, "not-matches", Kind::Scorer, Arity::One, "Filter where value doesn't match regex"),
+    flag(None, "gt", Kind::Scorer, Arity::One, "Filter where value is greater than number"),
+    flag(None, "lt", Kind::Scorer, Arity::One, "Filter where value is less than number"),
+    flag(None, "ge", Kind::Scorer, Arity::One, "Filter where value is greater than or equal to number"),
+    flag(None, "le", Kind::Scorer, Arity::One, "Filter where value is less than or equal to number"),
+    flag(None, "between", Kind::Scorer, Arity::Range, "Filter where value is within LOW..HIGH (inclusive)"),
+    flag(None, "newer", Kind::Scorer, Arity::One, "Filter where date is after a date or duration ago (e.g. 2d)"),
+    flag(None, "older", Kind::Scorer, Arity::One, "Filter where date is before a date or duration ago (e.g. 2026-01-01)"),
+    flag(None, "between-dates", Kind::Scorer, Arity::Range, "Filter where date is within FROM..TO (inclusive)"),
+
+    //Scorers
+    flag(Some('m'), "more", Kind::Scorer, Arity::One, "Increase based on matches"),
+    flag(Some('L'), "less", Kind::Scorer, Arity::One, "Decrease based on matches"),
+    flag(None, "more-re", Kind::Scorer, Arity::One, "Increase based on regex matches"),
+    flag(None, "less-re", Kind::Scorer, Arity::One, "Decrease based on regex matches"),
+
+    //Query operators
+    flag(None, "and", Kind::Operator, Arity::Zero, "Both surrounding runs must match (the default)"),
+    flag(None, "or", Kind::Operator, Arity::Zero, "Either surrounding run may match"),
+    flag(None, "not-run", Kind::Operator, Arity::Zero, "Negate the following run or group"),
+
+    //Specifiers
+    flag(Some('i'), "insensitive", Kind::Modifier, Arity::Zero, "Don't match case"),
+
+    //File & Directory operation
+    flag(Some('r'), "recursive", Kind::Switch, Arity::Zero, "Recursivelly descend"),
+    flag(Some('a'), "hidden", Kind::Switch, Arity::Zero, "Display hidden files and directories"),
+    flag(None, "no-ignore", Kind::Switch, Arity::Zero, "Don't respect .gitignore, .ignore or global ignore files"),
+    flag(None, "ignore-file", Kind::Setting, Arity::One, "Also ignore paths matching patterns in this file"),
+    flag(None, "max-depth", Kind::Setting, Arity::One, "Descend at most this many directories, implies --recursive"),
+    flag(None, "min-depth", Kind::Setting, Arity::One, "Skip entries shallower than this depth, implies --recursive"),
+    flag(None, "exclude", Kind::Setting, Arity::One, "Skip files and directories whose name matches this glob"),
+    flag(None, "exclude-dir", Kind::Setting, Arity::One, "Don't descend into directories whose name matches this glob"),
+    flag(None, "binary", Kind::Switch, Arity::Zero, "Search the text of binary files too"),
+    flag(None, "text-only", Kind::Switch, Arity::Zero, "Leave binary files out of the results"),
+
+    //Output
+    flag(Some('j'), "jobs", Kind::Setting, Arity::One, "Evaluate files on this many threads, 0 for one per core"),
+    flag(None, "exec-output", Kind::Setting, Arity::One, "Content of --content-exec: stdout, stderr or status"),
+    flag(None, "exec-timeout", Kind::Setting, Arity::One, "Kill commands still running after this long, e.g. 10 or 500ms"),
+    flag(None, "exec-jobs", Kind::Setting, Arity::One, "Run at most this many commands at once, 0 for no cap"),
+    flag(None, "exec-cache", Kind::Switch, Arity::Zero, "Reuse command results for files that haven't changed"),
+    flag(None, "quiet-errors", Kind::Switch, Arity::Zero, "Skip files that can't be searched without a warning"),
+    flag(None, "strict", Kind::Switch, Arity::Zero, "Stop at the first file that can't be searched"),
+    flag(None, "cache-size", Kind::Setting, Arity::One, "Memory cap for content shared between runs of one file, e.g. 64M"),
+    flag(None, "format", Kind::Setting, Arity::One, "Write results as text, json or ndjson"),
+    flag(None, "quoting-style", Kind::Setting, Arity::One, "Write names as literal, escape, c or shell-escape"),
+    flag(Some('1'), "one-per-line", Kind::Switch, Arity::Zero, "List one file per line"),
+    flag(Some('0'), "print0", Kind::Switch, Arity::Zero, "End each file with a NUL byte instead of a newline, for xargs -0"),
+    flag(None, "printf", Kind::Setting, Arity::One, "Print each file with a template, e.g. '{score} {path}\\n'"),
+    flag(None, "template", Kind::Setting, Arity::One, "Same as --printf"),
+    flag(None, "snippets", Kind::Switch, Arity::Zero, "Show the lines text runs matched under each file"),
+    flag(None, "context", Kind::Setting, Arity::One, "Show this many lines around each snippet, implies --snippets"),
+    flag(Some('l'), "long", Kind::Switch, Arity::Zero, "Long listing"),
+    flag(None, "human-readable", Kind::Switch, Arity::Zero, "Long listing sizes like 1K and 234M, -h when grouped with -l as in -lh"),
+    flag(None, "score", Kind::Switch, Arity::Zero, "Display score"),
+    flag(None, "stats", Kind::Switch, Arity::Zero, "Display statistics"),
+    flag(None, "strats", Kind::Switch, Arity::Zero, "Display decision strategy"),
+    flag(Some('A'), "absolute", Kind::Switch, Arity::Zero, "Print absolute path"),
+    flag(None, "echo", Kind::Switch, Arity::Zero, "Echo command"),
+    flag(None, "help", Kind::Switch, Arity::Zero, "Prints help information"),
+    flag(Some('V'), "version", Kind::Switch, Arity::Zero, "Prints version information"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    And,
+    Or,
+    Not,
+    Open,
+    Close
+}
+
+/// One position-sensitive element of the query, in the order it was given
+#[derive(Debug)]
+pub enum Item {
+    Loader { name: &'static str, value: Option<String> },
+    Scorer { name: &'static str, target: String },
+    Modifier { name: &'static str },
+    Operator(Operator)
+}
+
+#[derive(Debug, Default)]
+pub struct Query {
+    pub items: Vec<Item>,
+    pub paths: Vec<OsString>,
+    switches: Vec<&'static str>,
+    settings: Vec<(&'static str, String)>
+}
+
+impl Query {
+    pub fn is_present(&self, name: &str) -> bool {
+        self.switches.contains(&name) || self.settings.iter().any(|(setting, _)| *setting == name)
+    }
+
+    /// The last value given for a setting
+    pub fn value_of(&self, name: &str) -> Option<&str> {
+        self.values_of(name).pop()
+    }
+
+    pub fn values_of(&self, name: &str) -> Vec<&str> {
+        self.settings.iter()
+            .filter(|(setting, _)| *setting == name)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    message: String
+}
+
+impl ParseError {
+    fn new(message: String) -> ParseError {
+        ParseError { message }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "error: {}", self.message)?;
+        writeln!(f)?;
+        writeln!(f, "USAGE:")?;
+        writeln!(f, "    lsearch [FLAGS] [QUERY]... [--] [PATH]...")?;
+        writeln!(f)?;
+        write!(f, "For more information try --help")
+    }
+}
+
+fn find_long(long: &str) -> Option<&'static Flag> {
+    FLAGS.iter().find(|f| f.long == long)
+}
+
+fn find_short(short: char) -> Option<&'static Flag> {
+    FLAGS.iter().find(|f| f.short == Some(short))
+}
+
+/// Optimal string alignment distance, so that swapped letters like `hsa` count as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+fn unknown_long(long: &str) -> ParseError {
+    let suggestion = FLAGS.iter()
+        .map(|f| (edit_distance(long, f.long), f.long))
+        .filter(|(distance, name)| *distance <= 2.max(name.len() / 4))
+        .min_by_key(|(distance, _)| *distance);
+
+    match suggestion {
+        Some((_, name)) => ParseError::new(format!("unknown argument '--{}', did you mean '--{}'?", long, name)),
+        None => ParseError::new(format!("unknown argument '--{}'", long))
+    }
+}
+
+struct Parser<I: Iterator<Item = OsString>> {
+    args: I,
+    query: Query
+}
+
+impl<I: Iterator<Item = OsString>> Parser<I> {
+    /// Values are matched against text, so bytes that aren't UTF-8 are read lossily
+    fn next_value(&mut self, flag: &Flag) -> Result<String, ParseError> {
+        self.args.next()
+            .map(|value| value.to_string_lossy().into_owned())
+            .ok_or_else(|| ParseError::new(format!("'--{}' requires a value", flag.long)))
+    }
+
+    fn push(&mut self, flag: &'static Flag, inline: Option<String>) -> Result<(), ParseError> {
+        if flag.arity == Arity::Zero && inline.is_some() {
+            return Err(ParseError::new(format!("'--{}' doesn't take a value", flag.long)));
+        }
+
+        let value = match flag.arity {
+            Arity::Zero => None,
+            Arity::One => Some(match inline {
+                Some(value) => value,
+                None => self.next_value(flag)?
+            }),
+            Arity::Range => {
+                let low = match inline {
+                    Some(value) => value,
+                    None => self.next_value(flag)?
+                };
+
+                if low.contains("..") {
+                    Some(low)
+                }
+                else {
+                    let high = self.next_value(flag)?;
+                    Some(low + ".." + &high)
+                }
+            }
+        };
+
+        let item = match flag.kind {
+            Kind::Loader => Item::Loader { name: flag.long, value },
+            Kind::Scorer => Item::Scorer { name: flag.long, target: value.unwrap_or_default() },
+            Kind::Modifier => Item::Modifier { name: flag.long },
+            Kind::Operator => Item::Operator(match flag.long {
+                "and" => Operator::And,
+                "or" => Operator::Or,
+                _ => Operator::Not
+            }),
+            Kind::Switch => {
+                self.query.switches.push(flag.long);
+                return Ok(());
+            },
+            Kind::Setting => {
+                self.query.settings.push((flag.long, value.unwrap_or_default()));
+                return Ok(());
+            }
+        };
+
+        self.query.items.push(item);
+        Ok(())
+    }
+
+    fn parse_long(&mut self, arg: &str) -> Result<(), ParseError> {
+        let (long, inline) = match arg.split_once('=') {
+            Some((long, value)) => (long, Some(String::from(value))),
+            None => (arg, None)
+        };
+
+        match find_long(long) {
+            Some(flag) => self.push(flag, inline),
+            None => Err(unknown_long(long))
+        }
+    }
+
+    fn parse_shorts(&mut self, arg: &str) -> Result<(), ParseError> {
+        // -h is --has, except among switches alongside -l, so that ls habits like -lh still work
+        let listing = arg.contains('l') && arg.chars()
+            .all(|c| c == 'h' || find_short(c).is_some_and(|f| f.kind == Kind::Switch));
+
+        for (i, short) in arg.char_indices() {
+            let flag = match short {
+                'h' if listing => find_long("human-readable"),
+                _ => find_short(short)
+            }.ok_or_else(|| ParseError::new(format!("unknown flag '-{}' in '-{}'", short, arg)))?;
+
+            // As with getopt, the rest of the group is the value of a flag taking one
+            let rest = &arg[i + short.len_utf8()..];
+            if flag.arity != Arity::Zero && !rest.is_empty() {
+                return self.push(flag, Some(String::from(rest)));
+            }
+
+            self.push(flag, None)?;
+        }
+
+        Ok(())
+    }
+
+    fn parse(mut self) -> Result<Query, ParseError> {
+        while let Some(arg) = self.args.next() {
+            // Paths can be any bytes, flags never are
+            let arg = match arg.into_string() {
+                Ok(arg) => arg,
+                Err(path) => {
+                    self.query.paths.push(path);
+                    continue;
+                }
+            };
+
+            if arg == "--" {
+                self.query.paths.extend(self.args.by_ref());
+            }
+            else if arg == "(" {
+                self.query.items.push(Item::Operator(Operator::Open));
+            }
+            else if arg == ")" {
+                self.query.items.push(Item::Operator(Operator::Close));
+            }
+            else if let Some(long) = arg.strip_prefix("--") {
+                self.parse_long(long)?;
+            }
+            else if arg.len() > 1 && arg.starts_with('-') {
+                self.parse_shorts(&arg[1..])?;
+            }
+            else {
+                self.query.paths.push(OsString::from(arg));
+            }
+        }
+
+        Ok(self.query)
+    }
+}
+
+/// Parses command line arguments, not including the program name
+pub fn parse<I: Iterator<Item = OsString>>(args: I) -> Result<Query, ParseError> {
+    Parser { args, query: Query::default() }.parse()
+}
+
+pub fn print_version() {
+    println!("{} {}", NAME, VERSION);
+}
+
+pub fn print_help() {
+    println!("{} {}", NAME, VERSION);
+    println!("{}", AUTHOR);
+    println!("{}", ABOUT);
+    println!();
+    println!("USAGE:");
+    println!("    lsearch [FLAGS] [QUERY]... [--] [PATH]...");
+
+    let sections = [
+        ("CONTENT TYPES", Kind::Loader),
+        ("FILTERS AND SCORERS", Kind::Scorer),
+        ("QUERY OPERATORS", Kind::Operator),
+        ("MODIFIERS", Kind::Modifier),
+        ("FLAGS", Kind::Switch),
+        ("OPTIONS", Kind::Setting)
+    ];
+
+    for (title, kind) in sections.iter() {
+        println!();
+        println!("{}:", title);
+        if *kind == Kind::Operator {
+            println!("    {:<4}{:<32}Group runs", "", "( ... )");
+        }
+
+        for flag in FLAGS.iter().filter(|f| f.kind == *kind) {
+            let short = match flag.short {
+                Some(short) => format!("-{},", short),
+                None => String::new()
+            };
+            let value = match flag.arity {
+                Arity::Zero => "",
+                Arity::One => " <value>",
+                Arity::Range => " <low> <high>"
+            };
+            let usage = format!("--{}{}", flag.long, value);
+
+            println!("    {:<4}{:<32}{}", short, usage, flag.about);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Query, ParseError> {
+        parse(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn short_groups_split_like_getopt() {
+        let query = parse_args(&["-rt", "-hfoo", "-iebar"]).unwrap();
+        assert!(query.is_present("recursive"));
+        assert!(matches!(query.items[..], [
+            Item::Loader { name: "content-text", value: None },
+            Item::Scorer { name: "has", ref target },
+            Item::Modifier { name: "insensitive" },
+            Item::Scorer { name: "is", target: ref is }
+        ] if target == "foo" && is == "bar"));
+    }
+
+    #[test]
+    fn h_is_human_readable_only_beside_l() {
+        let query = parse_args(&["-lh"]).unwrap();
+        assert!(query.is_present("long") && query.is_present("human-readable"));
+        assert!(query.items.is_empty());
+
+        // With a flag that isn't a switch in the group, -h is --has again
+        let query = parse_args(&["-lth", "x"]).unwrap();
+        assert!(!query.is_present("human-readable"));
+        assert!(matches!(query.items[..], [Item::Loader { .. }, Item::Scorer { name: "has", ref target }] if target == "x"));
+    }
+
+    #[test]
+    fn inline_values_can_be_empty() {
+        let query = parse_args(&["--printf=", "--format=json"]).unwrap();
+        assert_eq!(query.value_of("printf"), Some(""));
+        assert_eq!(query.value_of("format"), Some("json"));
+
+        assert!(parse_args(&["--recursive="]).unwrap_err().message.contains("doesn't take a value"));
+    }
+
+    #[test]
+    fn ranges_take_one_or_two_values() {
+        for args in [&["--between", "1..2"][..], &["--between", "1", "2"], &["--between=1", "2"], &["--between=1..2"]] {
+            let query = parse_args(args).unwrap();
+            assert!(matches!(query.items[..], [Item::Scorer { name: "between", ref target }] if target == "1..2"), "{:?}", args);
+            assert!(query.paths.is_empty(), "{:?}", args);
+        }
+
+        assert!(parse_args(&["--between", "1"]).unwrap_err().message.contains("requires a value"));
+    }
+
+    #[test]
+    fn grouping_and_paths() {
+        let query = parse_args(&["(", "-t", "-h", "a", "--or", "-t", "-h", "b", ")", "src", "--", "-r"]).unwrap();
+        let operators: Vec<Operator> = query.items.iter()
+            .filter_map(|item| match item { Item::Operator(operator) => Some(*operator), _ => None })
+            .collect();
+        assert_eq!(operators, [Operator::Open, Operator::Or, Operator::Close]);
+        assert_eq!(query.paths, [OsString::from("src"), OsString::from("-r")]);
+        assert!(!query.is_present("recursive"));
+    }
+
+    #[test]
+    fn unknown_flags_suggest_the_closest() {
+        assert!(parse_args(&["--hsa", "x"]).unwrap_err().message.contains("did you mean '--has'?"));
+        assert!(parse_args(&["--recursve"]).unwrap_err().message.contains("did you mean '--recursive'?"));
+        assert!(!parse_args(&["--zzzzzz"]).unwrap_err().message.contains("did you mean"));
+    }
+}
@@ -2,31 +2,194 @@ pub mod loaders {
     use std::fs::File;
     use std::io::prelude::*;
     use std::io::BufReader;
-    use std::process::Command;
+    use std::process::{Command, Stdio};
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
     use std::path;
+    use std::sync::{Arc, Condvar, Mutex, OnceLock};
+    use std::os::unix::fs::MetadataExt;
+    use users::{get_user_by_uid, get_group_by_gid};
+    use chrono::prelude::*;
+
+    /// Format of timestamps produced by the time loaders, sortable as plain text
+    pub const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+    /// Bytes read at a time when content is streamed
+    pub const CHUNK_SIZE: usize = 64 * 1024;
+
+    /// Bytes at the start of a file looked at to tell whether it is binary
+    const BINARY_CHECK_SIZE: u64 = 8 * 1024;
+
+    /// Why content couldn't be loaded for a file
+    #[derive(Debug)]
+    pub struct LoadError {
+        path: path::PathBuf,
+        reason: String
+    }
+
+    impl LoadError {
+        pub fn new(path: &path::Path, reason: impl std::fmt::Display) -> LoadError {
+            LoadError { path: path.to_path_buf(), reason: reason.to_string() }
+        }
+    }
+
+    impl std::fmt::Display for LoadError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}: {}", self.path.display(), self.reason)
+        }
+    }
 
     #[derive(Debug)]
     pub struct FileData {
         path: path::PathBuf,
+        binary: OnceLock<bool>
     }
 
     impl FileData {
         pub fn new(path: path::PathBuf) -> FileData {
-            FileData { path }
+            FileData { path, binary: OnceLock::new() }
+        }
+
+        /// Whether the start of the file holds a NUL byte or is over a tenth control
+        /// characters. Read once, unreadable files and anything but regular files count as text.
+        pub fn is_binary(&self) -> bool {
+            *self.binary.get_or_init(|| {
+                if !self.is_file() {
+                    return false;
+                }
+
+                let mut block = Vec::new();
+                match File::open(&self.path) {
+                    Ok(file) => { let _ = file.take(BINARY_CHECK_SIZE).read_to_end(&mut block); },
+                    Err(_) => return false
+                }
+
+                let control = block.iter()
+                    .filter(|b| (**b < 0x20 && !b"\t\n\r\x0c\x1b\x08".contains(b)) || **b == 0x7f)
+                    .count();
+                block.contains(&0) || control * 10 > block.len()
+            })
+        }
+
+        /// Only regular files have text to read. Opening a FIFO could block forever, and
+        /// devices may never end.
+        pub fn is_file(&self) -> bool {
+            self.path.metadata().is_ok_and(|meta| meta.is_file())
+        }
+
+        /// True when the file was found to be binary, without reading it to find out
+        pub fn checked_binary(&self) -> bool {
+            self.binary.get() == Some(&true)
         }
 
         pub fn path(&self) -> &path::Path {
             self.path.as_path()
         }
 
-        pub fn metadata(&self) -> std::fs::Metadata {
-            self.path.metadata().expect("Unable to get metadata")
+        pub fn metadata(&self) -> Result<std::fs::Metadata, LoadError> {
+            // Broken symlinks have no target to describe, so fall back to the link itself
+            self.path.metadata()
+                .or_else(|_| self.path.symlink_metadata())
+                .map_err(|e| LoadError::new(&self.path, e))
+        }
+
+        fn local_time(&self, seconds: i64) -> Result<DateTime<Local>, LoadError> {
+            Local.timestamp_opt(seconds, 0).earliest()
+                .ok_or_else(|| LoadError::new(&self.path, format!("timestamp {} out of range", seconds)))
         }
     }
 
-    pub trait ContentLoader {
-        fn load_content(&self, entry: &FileData) -> String;
+    pub trait ContentLoader: Send + Sync {
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError>;
         fn get_name(&self) -> &str;
+
+        /// Loaders with the same key produce the same content for a file
+        fn cache_key(&self) -> String {
+            String::from(self.get_name())
+        }
+
+        /// Rough cost in nanoseconds of loading one file, used to order runs before any
+        /// have been measured. Loaders reading only the path are the cheapest.
+        fn cost(&self) -> f32 {
+            200.0
+        }
+
+        /// Whether the content is the file's own bytes, which binary files are skipped for
+        fn is_text(&self) -> bool {
+            false
+        }
+
+        /// Content a chunk at a time, for loaders whose content can be too big to hold whole
+        fn stream(&self, _entry: &FileData) -> Option<TextChunks> {
+            None
+        }
+    }
+
+    /// Reads content in chunks of up to `CHUNK_SIZE` bytes, decoded lossily as UTF-8. A
+    /// character split between two reads is held back until the rest of it arrives.
+    pub struct TextChunks {
+        reader: Box<dyn Read>,
+        pending: Vec<u8>,
+        done: bool
+    }
+
+    impl TextChunks {
+        pub fn new(reader: Box<dyn Read>) -> TextChunks {
+            TextChunks { reader, pending: Vec::new(), done: false }
+        }
+    }
+
+    impl Iterator for TextChunks {
+        type Item = std::io::Result<String>;
+
+        fn next(&mut self) -> Option<std::io::Result<String>> {
+            if self.done {
+                return None;
+            }
+
+            let mut buffer = vec![0; CHUNK_SIZE];
+            let read = loop {
+                match self.reader.read(&mut buffer) {
+                    Ok(read) => break read,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                }
+            };
+
+            if read == 0 {
+                self.done = true;
+                if self.pending.is_empty() {
+                    return None;
+                }
+                return Some(Ok(String::from_utf8_lossy(&std::mem::take(&mut self.pending)).into_owned()));
+            }
+
+            self.pending.extend_from_slice(&buffer[..read]);
+            let rest = self.pending.split_off(complete_utf8_len(&self.pending));
+            let chunk = String::from_utf8_lossy(&self.pending).into_owned();
+            self.pending = rest;
+            Some(Ok(chunk))
+        }
+    }
+
+    /// Length of `bytes` without a trailing character that is still missing bytes
+    fn complete_utf8_len(bytes: &[u8]) -> usize {
+        for back in 1..=bytes.len().min(3) {
+            let start = bytes.len() - back;
+            // Continuation bytes are 0b10xxxxxx, anything else starts a character
+            if bytes[start] & 0xC0 != 0x80 {
+                let width = match bytes[start] {
+                    0xC0..=0xDF => 2,
+                    0xE0..=0xEF => 3,
+                    0xF0..=0xF7 => 4,
+                    _ => 1
+                };
+                return if width > back { start } else { bytes.len() };
+            }
+        }
+        bytes.len()
     }
 
     pub fn parse(arg: &str) -> Option<Box<dyn ContentLoader>> {
@@ -35,6 +198,13 @@ pub mod loaders {
            "content-text" => Some(Box::new(ContentText::new())),
            "content-title" => Some(Box::new(ContentTitle::new())),
            "content-ext" => Some(Box::new(ContentExt::new())),
+           "content-size" => Some(Box::new(ContentSize::new())),
+           "content-owner" => Some(Box::new(ContentOwner::new())),
+           "content-group" => Some(Box::new(ContentGroup::new())),
+           "content-mode" => Some(Box::new(ContentMode::new())),
+           "content-mtime" => Some(Box::new(ContentMtime::new())),
+           "content-ctime" => Some(Box::new(ContentCtime::new())),
+           "content-atime" => Some(Box::new(ContentAtime::new())),
            _ => None 
        }
    }
@@ -47,11 +217,8 @@ pub mod loaders {
         }
     }
     impl ContentLoader for ContentTitle {
-        fn load_content(&self, entry: &FileData) -> String {
-            String::from(match entry.path.file_name().unwrap().to_str() {
-                Some(file_name) => {file_name},
-                None => { "" }
-            })
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError> {
+            Ok(entry.path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default())
         }
 
         fn get_name(&self) -> &str {
@@ -67,8 +234,8 @@ pub mod loaders {
         }
     }
     impl ContentLoader for ContentPath {
-        fn load_content(&self, entry: &FileData) -> String {
-            String::from(entry.path.to_str().unwrap())
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError> {
+            Ok(entry.path.to_string_lossy().into_owned())
         }
 
         fn get_name(&self) -> &str {
@@ -84,11 +251,11 @@ pub mod loaders {
         }
     }
     impl ContentLoader for ContentExt {
-        fn load_content(&self, entry: &FileData) -> String {
-            match entry.path.extension() {
-                Some(ext) => String::from(ext.to_str().unwrap()),
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError> {
+            Ok(match entry.path.extension() {
+                Some(ext) => ext.to_string_lossy().into_owned(),
                 None => String::from("")
-            }
+            })
         }
 
         fn get_name(&self) -> &str {
@@ -96,202 +263,1702 @@ pub mod loaders {
         }
     }
 
-    pub struct ContentText { 
+    pub struct ContentSize {
     }
-    impl ContentText {
-        pub fn new() -> ContentText {
-            ContentText{}
+    impl ContentSize {
+        pub fn new() -> ContentSize {
+            ContentSize{}
         }
     }
-    impl ContentLoader for ContentText {
-        fn load_content(&self, entry: &FileData) -> String {
-            if entry.path.is_dir() {
-                String::new()
-            }
-            else{
-                let mut contents = String::new();
-                let file = File::open(String::from(entry.path.to_str().unwrap())).unwrap();
-                let mut buf_reader = BufReader::new(file);
-                buf_reader.read_to_string(&mut contents).expect("Failed to read contents");
-                contents
-            }
+    impl ContentLoader for ContentSize {
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError> {
+            Ok(entry.metadata()?.len().to_string())
         }
 
         fn get_name(&self) -> &str {
-            "content-text"
+            "content-size"
+        }
+
+        fn cost(&self) -> f32 {
+            2000.0
         }
     }
 
-    pub struct ContentExec {
-        command: String 
+    pub struct ContentOwner {
     }
-    impl ContentExec {
-        pub fn new(command: &'_ str) -> ContentExec {
-            ContentExec{command: String::from(command)}
+    impl ContentOwner {
+        pub fn new() -> ContentOwner {
+            ContentOwner{}
         }
     }
-    impl ContentLoader for ContentExec {
-        fn load_content(&self, entry: &FileData) -> String {
-            let mut i = self.command.split(' ');
-            let mut cmd = Command::new(i.next().unwrap());
-
-            for arg in i {
-                cmd.arg(arg); 
-            }
-            
-            if let Some(file_name) = entry.path.file_name().unwrap().to_str() {
-                cmd.arg(file_name);
-                String::from_utf8(cmd.output().expect("Failed to run process").stdout).expect("Unable to parse output")
-            }
-            else {
-                String::new()
-            }
+    impl ContentLoader for ContentOwner {
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError> {
+            let uid = entry.metadata()?.uid();
+            Ok(match get_user_by_uid(uid) {
+                Some(user) => user.name().to_string_lossy().into_owned(),
+                None => uid.to_string()
+            })
         }
 
         fn get_name(&self) -> &str {
-            "content-exec"
+            "content-owner"
+        }
+
+        fn cost(&self) -> f32 {
+            2000.0
         }
     }
-}
 
-pub mod scorers {
-    pub fn create_key_from_scorer(scorer: &dyn ContentScorer, target: &str) -> String {
-        create_key(&scorer.get_name(), target)
+    pub struct ContentGroup {
     }
-    pub fn create_key(scorer: &str, target: &str) -> String {
-        String::from(scorer) + "(" + target + ")"
+    impl ContentGroup {
+        pub fn new() -> ContentGroup {
+            ContentGroup{}
+        }
     }
+    impl ContentLoader for ContentGroup {
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError> {
+            let gid = entry.metadata()?.gid();
+            Ok(match get_group_by_gid(gid) {
+                Some(group) => group.name().to_string_lossy().into_owned(),
+                None => gid.to_string()
+            })
+        }
 
-    pub mod fs {
-        pub trait DirEntryFilter: std::fmt::Debug {
-            fn filter(&self, content: &walkdir::DirEntry) -> bool;
+        fn get_name(&self) -> &str {
+            "content-group"
         }
 
-        #[derive(Debug)]
-        pub struct HiddenFilter {
-            allow: bool
+        fn cost(&self) -> f32 {
+            2000.0
         }
-        impl DirEntryFilter for HiddenFilter {
-            fn filter(&self, content: &walkdir::DirEntry) -> bool {
-                if self.allow {
-                    true
-                }
-                else {
-                    !content.file_name().to_str().unwrap().starts_with('.')
-                }
-            }
+    }
+
+    pub struct ContentMode {
+    }
+    impl ContentMode {
+        pub fn new() -> ContentMode {
+            ContentMode{}
         }
-        impl HiddenFilter {
-            pub fn _new(allow: bool) -> HiddenFilter {
-                HiddenFilter{ allow }
-            }
+    }
+    impl ContentLoader for ContentMode {
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError> {
+            Ok(format!("{:o}", entry.metadata()?.mode() & 0o7777))
+        }
+
+        fn get_name(&self) -> &str {
+            "content-mode"
+        }
+
+        fn cost(&self) -> f32 {
+            2000.0
         }
     }
 
-    pub trait ContentScorer: std::fmt::Debug {
-        fn score(&self, content: &str, target: &str) -> f32;
-        fn get_name(&self) -> String;
+    pub struct ContentMtime {
     }
-    pub trait ContentFilter: std::fmt::Debug {
-        fn filter(&self, content: &str, target: &str) -> bool;
+    impl ContentMtime {
+        pub fn new() -> ContentMtime {
+            ContentMtime{}
+        }
     }
+    impl ContentLoader for ContentMtime {
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError> {
+            let modified = entry.local_time(entry.metadata()?.mtime())?;
+            Ok(modified.format(TIME_FORMAT).to_string())
+        }
 
-    #[derive(Debug)]
-    pub struct Is {
+        fn get_name(&self) -> &str {
+            "content-mtime"
+        }
+
+        fn cost(&self) -> f32 {
+            2000.0
+        }
     }
-    impl ContentFilter for Is {
-        fn filter(&self, content: &str, target: &str) -> bool {
-            target.eq(content)
+
+    pub struct ContentCtime {
+    }
+    impl ContentCtime {
+        pub fn new() -> ContentCtime {
+            ContentCtime{}
         }
     }
-    impl ContentScorer for Is {
-        fn score(&self, content: &str, target: &str) -> f32 {
-            if self.filter(&content, &target) {1.0} else {0.0}
+    impl ContentLoader for ContentCtime {
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError> {
+            let changed = entry.local_time(entry.metadata()?.ctime())?;
+            Ok(changed.format(TIME_FORMAT).to_string())
         }
-        fn get_name(&self) -> String {
-            String::from("Is")
+
+        fn get_name(&self) -> &str {
+            "content-ctime"
+        }
+
+        fn cost(&self) -> f32 {
+            2000.0
         }
     }
 
-    #[derive(Debug)]
-    pub struct Not {
+    pub struct ContentAtime {
     }
-    impl ContentFilter for Not {
-        fn filter(&self, content: &str, target: &str) -> bool {
-            !target.eq(content)
+    impl ContentAtime {
+        pub fn new() -> ContentAtime {
+            ContentAtime{}
         }
     }
-    impl ContentScorer for Not {
-        fn score(&self, content: &str, target: &str) -> f32 {
-            if self.filter(&content, &target) {1.0} else {0.0}
+    impl ContentLoader for ContentAtime {
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError> {
+            let accessed = entry.local_time(entry.metadata()?.atime())?;
+            Ok(accessed.format(TIME_FORMAT).to_string())
         }
-        fn get_name(&self) -> String {
-            String::from("Not")
+
+        fn get_name(&self) -> &str {
+            "content-atime"
+        }
+
+        fn cost(&self) -> f32 {
+            2000.0
         }
     }
 
-    #[derive(Debug)]
-    pub struct Has {
+    pub struct ContentText { 
     }
-    impl ContentFilter for Has {
-        fn filter(&self, content: &str, target: &str) -> bool {
-            content.contains(target)
+    impl ContentText {
+        pub fn new() -> ContentText {
+            ContentText{}
         }
     }
-    impl ContentScorer for Has {
-        fn score(&self, content: &str, target: &str) -> f32 {
-            if self.filter(&content, &target) {1.0} else {0.0}
+    impl ContentLoader for ContentText {
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError> {
+            // Directories, FIFOs, sockets and devices have no text
+            if !entry.path.metadata().map_err(|e| LoadError::new(&entry.path, e))?.is_file() {
+                Ok(String::new())
+            }
+            else{
+                let mut contents = Vec::new();
+                let file = File::open(&entry.path).map_err(|e| LoadError::new(&entry.path, e))?;
+                let mut buf_reader = BufReader::new(file);
+                buf_reader.read_to_end(&mut contents).map_err(|e| LoadError::new(&entry.path, e))?;
+                Ok(String::from_utf8_lossy(&contents).into_owned())
+            }
         }
-        fn get_name(&self) -> String {
-            String::from("Has")
+
+        fn get_name(&self) -> &str {
+            "content-text"
+        }
+
+        fn cost(&self) -> f32 {
+            50000.0
+        }
+
+        fn is_text(&self) -> bool {
+            true
+        }
+
+        fn stream(&self, entry: &FileData) -> Option<TextChunks> {
+            if !entry.is_file() {
+                return None;
+            }
+            File::open(&entry.path).ok().map(|file| TextChunks::new(Box::new(file)))
         }
     }
 
-    #[derive(Debug)]
-    pub struct Hasnt {
+    /// What of a command's result becomes the content
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ExecOutput {
+        Stdout,
+        Stderr,
+        /// The exit code, or 128 plus the signal that killed it
+        Status
     }
-    impl ContentFilter for Hasnt {
-        fn filter(&self, content: &str, target: &str) -> bool {
-            !content.contains(target)
+
+    impl ExecOutput {
+        pub fn parse(output: &str) -> Option<ExecOutput> {
+            match output {
+                "stdout" => Some(ExecOutput::Stdout),
+                "stderr" => Some(ExecOutput::Stderr),
+                "status" => Some(ExecOutput::Status),
+                _ => None
+            }
         }
     }
-    impl ContentScorer for Hasnt {
-        fn score(&self, content: &str, target: &str) -> f32 {
-            if self.filter(&content, &target) {1.0} else {0.0}
-        }
-        fn get_name(&self) -> String {
-            String::from("Hasnt")
+
+    /// Splits a command line into words like a POSIX shell would: quotes group words, and a
+    /// backslash escapes the next character outside single quotes
+    fn split_command(command: &str) -> Result<Vec<String>, String> {
+        let unterminated = || format!("unterminated quote in command '{}'", command);
+        let mut words = Vec::new();
+        // Starts on the first character of a word, so that '' is still a word
+        let mut word: Option<String> = None;
+        let mut chars = command.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                c if c.is_whitespace() => words.extend(word.take()),
+                '\'' => {
+                    let word = word.get_or_insert_with(String::new);
+                    loop {
+                        match chars.next() {
+                            Some('\'') => break,
+                            Some(c) => word.push(c),
+                            None => return Err(unterminated())
+                        }
+                    }
+                },
+                '"' => {
+                    let word = word.get_or_insert_with(String::new);
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => match chars.next() {
+                                Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                                Some(c) => { word.push('\\'); word.push(c); },
+                                None => return Err(unterminated())
+                            },
+                            Some(c) => word.push(c),
+                            None => return Err(unterminated())
+                        }
+                    }
+                },
+                '\\' => match chars.next() {
+                    Some(c) => word.get_or_insert_with(String::new).push(c),
+                    None => return Err(format!("trailing backslash in command '{}'", command))
+                },
+                c => word.get_or_insert_with(String::new).push(c)
+            }
         }
+
+        words.extend(word);
+        Ok(words)
     }
 
-    #[derive(Debug)]
-    pub struct More {
+    /// Placeholders as in GNU parallel, longest first so `{//}` isn't read as `{/}`
+    const PLACEHOLDERS: [&str; 5] = ["{//}", "{/.}", "{/}", "{.}", "{}"];
+
+    fn placeholder_value(placeholder: &str, path: &path::Path) -> std::ffi::OsString {
+        let value = match placeholder {
+            "{}" => Some(path.as_os_str().to_owned()),
+            "{/}" => path.file_name().map(|name| name.to_owned()),
+            "{//}" => path.parent().map(|parent| parent.as_os_str().to_owned()),
+            "{.}" => Some(path.with_extension("").into_os_string()),
+            "{/.}" => path.file_stem().map(|stem| stem.to_owned()),
+            _ => None
+        };
+        value.unwrap_or_else(|| path.as_os_str().to_owned())
     }
-    impl ContentScorer for More {
-        fn score(&self, content: &str, target: &str) -> f32 {
-            let mut score = 1.0;
 
-            for _m in content.matches(target) {
-                score += 1.0;
+    /// Replaces the placeholders in a word with parts of `path`
+    fn expand_word(word: &str, path: &path::Path) -> std::ffi::OsString {
+        let mut expanded = std::ffi::OsString::new();
+        let mut rest = word;
+
+        while let Some(start) = rest.find('{') {
+            expanded.push(&rest[..start]);
+            rest = &rest[start..];
+
+            match PLACEHOLDERS.iter().find(|placeholder| rest.starts_with(*placeholder)) {
+                Some(placeholder) => {
+                    expanded.push(placeholder_value(placeholder, path));
+                    rest = &rest[placeholder.len()..];
+                },
+                None => {
+                    expanded.push("{");
+                    rest = &rest[1..];
+                }
             }
+        }
 
-            score
+        expanded.push(rest);
+        expanded
+    }
+
+    fn is_executable(path: &path::Path) -> bool {
+        path.metadata().map(|meta| meta.is_file() && meta.mode() & 0o111 != 0).unwrap_or(false)
+    }
+
+    /// Whether `program` can be run, either as a path or found in `$PATH`
+    fn find_program(program: &str) -> bool {
+        if program.contains('/') {
+            return is_executable(path::Path::new(program));
         }
-        fn get_name(&self) -> String {
-            String::from("More")
+
+        match std::env::var_os("PATH") {
+            Some(paths) => std::env::split_paths(&paths).any(|dir| is_executable(&dir.join(program))),
+            None => false
         }
     }
 
+    /// Caps how many child processes run at once, across all threads
     #[derive(Debug)]
-    pub struct Pass {
+    pub struct ExecSlots {
+        running: Mutex<usize>,
+        freed: Condvar,
+        /// 0 for no cap
+        limit: usize
+    }
 
+    /// A running child's place, given back when dropped
+    struct ExecSlot<'a> {
+        slots: &'a ExecSlots
     }
-    impl ContentScorer for Pass {
-        fn score(&self, _content: &str, _target: &str) -> f32 {
-            1.0
+
+    impl ExecSlots {
+        pub fn new(limit: usize) -> ExecSlots {
+            ExecSlots { running: Mutex::new(0), freed: Condvar::new(), limit }
         }
-        fn get_name(&self) -> String {
-            String::from("Pass")
+
+        fn acquire(&self) -> ExecSlot<'_> {
+            let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+            while self.limit > 0 && *running >= self.limit {
+                running = self.freed.wait(running).unwrap_or_else(|e| e.into_inner());
+            }
+            *running += 1;
+            ExecSlot { slots: self }
+        }
+    }
+
+    impl Drop for ExecSlot<'_> {
+        fn drop(&mut self) {
+            *self.slots.running.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+            self.slots.freed.notify_one();
+        }
+    }
+
+    /// How `ContentExec` runs its commands, shared by every exec run of a search
+    #[derive(Debug, Clone)]
+    pub struct ExecSettings {
+        output: ExecOutput,
+        timeout: Option<std::time::Duration>,
+        slots: Arc<ExecSlots>,
+        /// Keep results on disk between searches
+        cache: bool
+    }
+
+    impl ExecSettings {
+        pub fn new(output: ExecOutput, timeout: Option<std::time::Duration>, jobs: usize, cache: bool) -> ExecSettings {
+            ExecSettings { output, timeout, slots: Arc::new(ExecSlots::new(jobs)), cache }
+        }
+
+        pub fn with_output(&self, output: ExecOutput) -> ExecSettings {
+            ExecSettings { output, ..self.clone() }
+        }
+    }
+
+    /// `$XDG_CACHE_HOME/lsearch`, otherwise `~/.cache/lsearch`
+    pub fn cache_dir() -> Option<path::PathBuf> {
+        let cache = std::env::var_os("XDG_CACHE_HOME").map(path::PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| path::PathBuf::from(home).join(".cache")))?;
+        Some(cache.join("lsearch"))
+    }
+
+    /// Replaces `path` with `contents` through a temporary file beside it, so a reader never sees
+    /// half of it. The temporary name is unique to the process and the write, as other searches
+    /// may be replacing the same file at once.
+    pub fn write_replacing(path: &path::Path, contents: &[u8]) -> std::io::Result<()> {
+        static WRITES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
+
+        let write = WRITES.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let temporary = path.with_extension(format!("{}-{}.tmp", std::process::id(), write));
+        let result = std::fs::write(&temporary, contents).and_then(|_| std::fs::rename(&temporary, path));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temporary);
+        }
+        result
+    }
+
+    /// Results kept by `--exec-cache` before the oldest are dropped
+    const EXEC_CACHE_LIMIT: u64 = 256 * 1024 * 1024;
+
+    /// Drops the oldest `--exec-cache` results until the rest fit under `EXEC_CACHE_LIMIT`. Runs
+    /// once per process, however many searches use the cache.
+    pub fn trim_exec_cache() {
+        static TRIMMED: std::sync::Once = std::sync::Once::new();
+
+        TRIMMED.call_once(|| {
+            let entries = match cache_dir().map(|dir| std::fs::read_dir(dir.join("exec"))) {
+                Some(Ok(entries)) => entries,
+                _ => return
+            };
+
+            let mut files: Vec<(i64, u64, path::PathBuf)> = entries.flatten()
+                .filter_map(|entry| entry.metadata().ok().filter(|meta| meta.is_file()).map(|meta| (meta.mtime(), meta.len(), entry.path())))
+                .collect();
+            let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+
+            files.sort();
+            for (_, len, path) in files {
+                if total <= EXEC_CACHE_LIMIT {
+                    break;
+                }
+                if std::fs::remove_file(path).is_ok() {
+                    total -= len;
+                }
+            }
+        });
+    }
+
+    /// Kills everything a command started, not just the command, as a shell's children would
+    /// otherwise live on holding the pipe open
+    fn kill_group(child: &mut std::process::Child) {
+        // The child leads its own group, so its pid is the group's id
+        unsafe {
+            libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+        }
+        let _ = child.wait();
+    }
+
+    /// Runs `cmd` to the end, or until `timeout` when it and anything it started are killed and
+    /// `None` returned. Only the stream `output` asks for is read.
+    fn run_command(mut cmd: Command, output: ExecOutput, timeout: Option<std::time::Duration>) -> std::io::Result<Option<(std::process::ExitStatus, Vec<u8>)>> {
+        let piped = |stream| if output == stream { Stdio::piped() } else { Stdio::null() };
+        cmd.stdin(Stdio::null())
+            .stdout(piped(ExecOutput::Stdout))
+            .stderr(piped(ExecOutput::Stderr))
+            .process_group(0);
+        let mut child = cmd.spawn()?;
+
+        // Read on another thread, so a child blocked on a full pipe still gets to exit
+        let pipe: Option<Box<dyn Read + Send>> = match (child.stdout.take(), child.stderr.take()) {
+            (Some(stdout), _) => Some(Box::new(stdout)),
+            (None, Some(stderr)) => Some(Box::new(stderr)),
+            (None, None) => None
+        };
+        let reader = pipe.map(|mut pipe| {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            std::thread::spawn(move || {
+                let mut content = Vec::new();
+                let _ = pipe.read_to_end(&mut content);
+                let _ = sender.send(content);
+            });
+            receiver
+        });
+
+        let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+        let status = match deadline {
+            None => child.wait()?,
+            Some(deadline) => {
+                let mut poll = std::time::Duration::from_millis(1);
+                loop {
+                    if let Some(status) = child.try_wait()? {
+                        break status;
+                    }
+                    if std::time::Instant::now() >= deadline {
+                        kill_group(&mut child);
+                        return Ok(None);
+                    }
+                    std::thread::sleep(poll);
+                    poll = (poll * 2).min(std::time::Duration::from_millis(50));
+                }
+            }
+        };
+
+        // What the command started in the background can keep the pipe open past its exit
+        let content = match (reader, deadline) {
+            (None, _) => Vec::new(),
+            (Some(reader), None) => reader.recv().unwrap_or_default(),
+            (Some(reader), Some(deadline)) => match reader.recv_timeout(deadline.saturating_duration_since(std::time::Instant::now())) {
+                Ok(content) => content,
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => Vec::new(),
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                    kill_group(&mut child);
+                    return Ok(None);
+                }
+            }
+        };
+        Ok(Some((status, content)))
+    }
+
+    pub struct ContentExec {
+        command: String,
+        words: Vec<String>,
+        /// Without placeholders the full path is added as the last argument
+        has_placeholder: bool,
+        settings: ExecSettings
+    }
+    impl ContentExec {
+        pub fn new(command: &'_ str, settings: ExecSettings) -> Result<ContentExec, String> {
+            let words = split_command(command)?;
+            let program = words.first().ok_or_else(|| String::from("empty command"))?;
+            if !find_program(program) {
+                return Err(format!("command not found: '{}'", program));
+            }
+
+            let has_placeholder = words[1..].iter().any(|word| PLACEHOLDERS.iter().any(|placeholder| word.contains(placeholder)));
+            Ok(ContentExec{command: String::from(command), words, has_placeholder, settings})
+        }
+
+        /// Identifies a result by command and file version: the path, mtime and size
+        fn disk_cache_key(&self, entry: &FileData) -> Option<Vec<u8>> {
+            use std::os::unix::ffi::OsStrExt;
+
+            let meta = entry.metadata().ok()?;
+            let mut key = format!("{:?}\0{}\0", self.settings.output, self.command).into_bytes();
+            key.extend_from_slice(entry.path.as_os_str().as_bytes());
+            key.extend_from_slice(format!("\0{}.{}\0{}", meta.mtime(), meta.mtime_nsec(), meta.len()).as_bytes());
+            Some(key)
+        }
+
+        fn disk_cache_path(key: &[u8]) -> Option<path::PathBuf> {
+            use std::hash::{Hash, Hasher};
+
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            key.hash(&mut hasher);
+            Some(cache_dir()?.join("exec").join(format!("{:016x}", hasher.finish())))
+        }
+
+        /// Entries hold the full key ahead of the content, so a hash collision is a miss
+        fn read_cached(key: &[u8]) -> Option<String> {
+            let data = std::fs::read(Self::disk_cache_path(key)?).ok()?;
+            let content = data.strip_prefix(key)?.strip_prefix(b"\n")?;
+            String::from_utf8(content.to_vec()).ok()
+        }
+
+        /// Failing to cache only costs running the command again
+        fn write_cached(key: &[u8], content: &str) {
+            let path = match Self::disk_cache_path(key) {
+                Some(path) => path,
+                None => return
+            };
+
+            let mut data = key.to_vec();
+            data.push(b'\n');
+            data.extend_from_slice(content.as_bytes());
+            let _ = write_replacing(&path, &data);
+        }
+
+        fn run(&self, entry: &FileData) -> Result<String, LoadError> {
+            let mut cmd = Command::new(&self.words[0]);
+
+            for word in &self.words[1..] {
+                cmd.arg(expand_word(word, &entry.path));
+            }
+            if !self.has_placeholder {
+                cmd.arg(&entry.path);
+            }
+
+            let _slot = self.settings.slots.acquire();
+            let (status, content) = match run_command(cmd, self.settings.output, self.settings.timeout) {
+                Ok(Some(result)) => result,
+                Ok(None) => return Err(LoadError::new(&entry.path, format!("'{}' timed out after {:?}", self.words[0], self.settings.timeout.unwrap_or_default()))),
+                Err(e) => return Err(LoadError::new(&entry.path, format!("failed to run '{}': {}", self.words[0], e)))
+            };
+
+            Ok(match self.settings.output {
+                ExecOutput::Stdout | ExecOutput::Stderr => String::from_utf8_lossy(&content).into_owned(),
+                ExecOutput::Status => match (status.code(), status.signal()) {
+                    (Some(code), _) => code.to_string(),
+                    (None, Some(signal)) => (128 + signal).to_string(),
+                    (None, None) => String::new()
+                }
+            })
+        }
+    }
+    impl ContentLoader for ContentExec {
+        fn load_content(&self, entry: &FileData) -> Result<String, LoadError> {
+            let key = if self.settings.cache { self.disk_cache_key(entry) } else { None };
+
+            if let Some(content) = key.as_deref().and_then(Self::read_cached) {
+                return Ok(content);
+            }
+
+            let content = self.run(entry)?;
+            if let Some(key) = key {
+                Self::write_cached(&key, &content);
+            }
+            Ok(content)
+        }
+
+        fn get_name(&self) -> &str {
+            "content-exec"
+        }
+
+        fn cost(&self) -> f32 {
+            2000000.0
+        }
+
+        fn cache_key(&self) -> String {
+            format!("{} {:?} {}", self.get_name(), self.settings.output, self.command)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn split(command: &str) -> Vec<String> {
+            split_command(command).unwrap()
+        }
+
+        #[test]
+        fn commands_split_like_a_shell() {
+            assert_eq!(split("stat  -c %U"), ["stat", "-c", "%U"]);
+            assert_eq!(split("grep -c 'a b' \"c d\""), ["grep", "-c", "a b", "c d"]);
+            assert_eq!(split("echo '' x"), ["echo", "", "x"]);
+            assert_eq!(split("echo a\\ b"), ["echo", "a b"]);
+            assert_eq!(split("echo 'it'\\''s'"), ["echo", "it's"]);
+            assert_eq!(split("echo \"\\\"\\$x\\n\""), ["echo", "\"$x\\n"]);
+            assert_eq!(split("echo 'a\\b'"), ["echo", "a\\b"]);
+            assert!(split("").is_empty());
+
+            assert!(split_command("echo 'a").is_err());
+            assert!(split_command("echo \"a").is_err());
+            assert!(split_command("echo a\\").is_err());
+        }
+
+        #[test]
+        fn placeholders_expand_to_parts_of_the_path() {
+            let path = path::Path::new("/tmp/dir/file.tar.gz");
+            let expand = |word| expand_word(word, path).into_string().unwrap();
+
+            assert_eq!(expand("{}"), "/tmp/dir/file.tar.gz");
+            assert_eq!(expand("{/}"), "file.tar.gz");
+            assert_eq!(expand("{//}"), "/tmp/dir");
+            assert_eq!(expand("{.}"), "/tmp/dir/file.tar");
+            assert_eq!(expand("{/.}"), "file.tar");
+            assert_eq!(expand("--in={}:{/.}.txt"), "--in=/tmp/dir/file.tar.gz:file.tar.txt");
+            assert_eq!(expand("{x} { }{"), "{x} { }{");
+            assert_eq!(expand("plain"), "plain");
+        }
+    }
+}
+
+pub mod scorers {
+    use chrono::prelude::*;
+    use chrono::Duration;
+
+    pub fn create_key_from_scorer(scorer: &dyn ContentScorer, target: &str) -> String {
+        create_key(&scorer.get_name(), target)
+    }
+    pub fn create_key(scorer: &str, target: &str) -> String {
+        String::from(scorer) + "(" + target + ")"
+    }
+
+    pub fn parse(arg: &str, target: &str) -> Result<Option<Box<dyn ContentScorer>>, String> {
+        Ok(match arg {
+            "is" => Some(Box::new(Is{})),
+            "not" => Some(Box::new(Not{})),
+            "has" => Some(Box::new(Has{})),
+            "hasnt" => Some(Box::new(Hasnt{})),
+            "more" => Some(Box::new(More{})),
+            "less" => Some(Box::new(Less{})),
+            "matches" => Some(Box::new(Matches::new(target)?)),
+            "not-matches" => Some(Box::new(NotMatches::new(target)?)),
+            "more-re" => Some(Box::new(MoreRe::new(target)?)),
+            "less-re" => Some(Box::new(LessRe::new(target)?)),
+            "gt" => Some(Box::new(Gt::new(target)?)),
+            "lt" => Some(Box::new(Lt::new(target)?)),
+            "ge" => Some(Box::new(Ge::new(target)?)),
+            "le" => Some(Box::new(Le::new(target)?)),
+            "between" => Some(Box::new(Between::new(target)?)),
+            "newer" => Some(Box::new(Newer::new(target)?)),
+            "older" => Some(Box::new(Older::new(target)?)),
+            "between-dates" => Some(Box::new(BetweenDates::new(target)?)),
+            _ => None
+        })
+    }
+
+    /// Parses a number with an optional binary size suffix, e.g. `1.5`, `10K`, `2MiB`
+    pub fn parse_number(value: &str) -> Option<f64> {
+        let value = value.trim();
+        let split = value.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(value.len());
+        let (number, suffix) = value.split_at(split);
+        let number: f64 = number.trim().parse().ok()?;
+
+        let exponent = match suffix.to_ascii_lowercase().as_str() {
+            "" | "b" => 0,
+            "k" | "kb" | "kib" => 1,
+            "m" | "mb" | "mib" => 2,
+            "g" | "gb" | "gib" => 3,
+            "t" | "tb" | "tib" => 4,
+            "p" | "pb" | "pib" => 5,
+            _ => return None
+        };
+
+        Some(number * 1024f64.powi(exponent))
+    }
+
+    fn parse_target(target: &str) -> Result<f64, String> {
+        parse_number(target).ok_or_else(|| format!("invalid number '{}'", target))
+    }
+
+    fn parse_range(target: &str) -> Result<(f64, f64), String> {
+        match target.split_once("..") {
+            Some((low, high)) => Ok((parse_target(low)?, parse_target(high)?)),
+            None => Err(format!("invalid range '{}', expected LOW..HIGH", target))
+        }
+    }
+
+    /// Parses an absolute date, a unix timestamp written `@N` as for `date -d`, or a duration
+    /// before now such as `2d` or `3h`
+    pub fn parse_datetime(value: &str) -> Option<DateTime<Local>> {
+        let value = value.trim();
+
+        if let Some(ago) = parse_duration(value) {
+            return Some(Local::now() - ago);
+        }
+
+        match value {
+            "now" => return Some(Local::now()),
+            "today" => return Local::now().date_naive().and_hms_opt(0, 0, 0)?.and_local_timezone(Local).earliest(),
+            "yesterday" => return (Local::now().date_naive() - Duration::days(1)).and_hms_opt(0, 0, 0)?.and_local_timezone(Local).earliest(),
+            _ => {}
+        }
+
+        if let Some(timestamp) = value.strip_prefix('@') {
+            return Local.timestamp_opt(timestamp.parse().ok()?, 0).single();
+        }
+
+        parse_content_datetime(value)
+    }
+
+    /// Parses an absolute time: as the time loaders write it, RFC 3339, or a date with or without
+    /// a time of day. Unlike `parse_datetime` nothing relative is accepted, so content such as
+    /// `5m` or `@1700000000` is never taken for a time.
+    fn parse_content_datetime(value: &str) -> Option<DateTime<Local>> {
+        let value = value.trim();
+
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+            return Some(datetime.with_timezone(&Local));
+        }
+
+        if let Ok(datetime) = DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f %z") {
+            return Some(datetime.with_timezone(&Local));
+        }
+
+        for format in &[super::loaders::TIME_FORMAT, "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"] {
+            if let Ok(datetime) = NaiveDateTime::parse_from_str(value, format) {
+                return datetime.and_local_timezone(Local).earliest();
+            }
+        }
+
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?
+            .and_hms_opt(0, 0, 0)?
+            .and_local_timezone(Local)
+            .earliest()
+    }
+
+    /// Parses durations like `500ms`, `90s`, `30m`, `3h`, `2d`, `1w` or `1y`
+    pub fn parse_duration(value: &str) -> Option<Duration> {
+        let split = value.find(|c: char| !c.is_ascii_digit())?;
+        let (number, unit) = value.split_at(split);
+        let number: i64 = number.parse().ok()?;
+
+        match unit {
+            "ms" => Some(Duration::milliseconds(number)),
+            "s" => Some(Duration::seconds(number)),
+            "m" | "min" => Some(Duration::minutes(number)),
+            "h" => Some(Duration::hours(number)),
+            "d" => Some(Duration::days(number)),
+            "w" => Some(Duration::weeks(number)),
+            "y" => Some(Duration::days(365 * number)),
+            _ => None
+        }
+    }
+
+    fn parse_date_target(target: &str) -> Result<DateTime<Local>, String> {
+        parse_datetime(target).ok_or_else(|| format!("invalid date '{}'", target))
+    }
+
+    /// Whether a pattern finds the same matches in content searched a few lines at a time as in
+    /// the whole of it. Anchors would match at every cut, patterns that can match a line break
+    /// could span one, and empty matches would be counted again at each cut.
+    fn is_line_local(pattern: &str) -> bool {
+        use regex_syntax::hir::{Class, Hir, HirKind};
+
+        fn matches_line_break(hir: &Hir) -> bool {
+            match hir.kind() {
+                HirKind::Literal(literal) => literal.0.contains(&b'\n'),
+                HirKind::Class(Class::Unicode(class)) => class.ranges().iter().any(|range| range.start() <= '\n' && '\n' <= range.end()),
+                HirKind::Class(Class::Bytes(class)) => class.ranges().iter().any(|range| range.start() <= b'\n' && b'\n' <= range.end()),
+                HirKind::Repetition(repetition) => matches_line_break(&repetition.sub),
+                HirKind::Capture(capture) => matches_line_break(&capture.sub),
+                HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs.iter().any(matches_line_break),
+                HirKind::Empty | HirKind::Look(..) => false
+            }
+        }
+
+        match regex_syntax::parse(pattern) {
+            Ok(hir) => !hir.properties().look_set().contains_anchor()
+                && hir.properties().minimum_len().is_some_and(|len| len > 0)
+                && !matches_line_break(&hir),
+            Err(..) => false
+        }
+    }
+
+    fn build_regex(pattern: &str, insensitive: bool) -> Result<regex::Regex, String> {
+        regex::RegexBuilder::new(pattern)
+            .case_insensitive(insensitive)
+            .build()
+            .map_err(|e| format!("invalid regex '{}': {}", pattern, e))
+    }
+
+    pub mod fs {
+        use std::collections::HashMap;
+        use std::path::{Path, PathBuf};
+        use std::sync::Mutex;
+        use ignore::gitignore::{Gitignore, GitignoreBuilder};
+        use ignore::Match;
+
+        pub trait DirEntryFilter: std::fmt::Debug {
+            fn filter(&self, content: &walkdir::DirEntry) -> bool;
+        }
+
+        #[derive(Debug)]
+        pub struct HiddenFilter {
+            allow: bool
+        }
+        impl DirEntryFilter for HiddenFilter {
+            fn filter(&self, content: &walkdir::DirEntry) -> bool {
+                if self.allow {
+                    true
+                }
+                else {
+                    !content.file_name().to_string_lossy().starts_with('.')
+                }
+            }
+        }
+        impl HiddenFilter {
+            pub fn new(allow: bool) -> HiddenFilter {
+                HiddenFilter{ allow }
+            }
+        }
+
+        /// Drops files whose name matches `--exclude`, and directories whose name matches `--exclude-dir`
+        #[derive(Debug)]
+        pub struct ExcludeFilter {
+            files: Vec<glob::Pattern>,
+            directories: Vec<glob::Pattern>
+        }
+        impl DirEntryFilter for ExcludeFilter {
+            fn filter(&self, content: &walkdir::DirEntry) -> bool {
+                !self.is_excluded(content.path(), content.file_type().is_dir())
+            }
+        }
+        impl ExcludeFilter {
+            pub fn new(files: Vec<glob::Pattern>, directories: Vec<glob::Pattern>) -> ExcludeFilter {
+                ExcludeFilter{ files, directories }
+            }
+
+            pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+                let name = match path.file_name() {
+                    Some(name) => name.to_string_lossy(),
+                    None => return false
+                };
+
+                self.files.iter().any(|p| p.matches(&name))
+                    || (is_dir && self.directories.iter().any(|p| p.matches(&name)))
+            }
+        }
+
+        /// Honors `.gitignore`, `.ignore`, `.git/info/exclude`, the global git excludes file and any extra ignore files
+        #[derive(Debug)]
+        pub struct IgnoreFilter {
+            directories: Mutex<HashMap<PathBuf, Gitignore>>,
+            global: Gitignore,
+            extra: Vec<Gitignore>
+        }
+        impl DirEntryFilter for IgnoreFilter {
+            fn filter(&self, content: &walkdir::DirEntry) -> bool {
+                !self.is_ignored(content.path(), content.file_type().is_dir(), content.depth())
+            }
+        }
+        impl IgnoreFilter {
+            pub fn new(ignore_files: &[PathBuf]) -> IgnoreFilter {
+                let (global, _) = Gitignore::global();
+                let extra = ignore_files.iter()
+                    .map(|file| Gitignore::new(file).0)
+                    .collect();
+
+                IgnoreFilter{ directories: Mutex::new(HashMap::new()), global, extra }
+            }
+
+            fn load(directory: &Path) -> Gitignore {
+                let mut builder = GitignoreBuilder::new(directory);
+
+                // Added from lowest to highest precedence, as later patterns win
+                let files = [directory.join(".git/info/exclude"), directory.join(".gitignore"), directory.join(".ignore")];
+                for file in files.iter().filter(|f| f.is_file()) {
+                    builder.add(file);
+                }
+
+                builder.build().unwrap_or_else(|_| Gitignore::empty())
+            }
+
+            /// Checks `path` and its parents up to `root` against one matcher, though no higher than
+            /// the `depth` levels the walk went down. The walk root and what is above it were named
+            /// on the command line, so they are never what makes an entry ignored.
+            fn matched(gitignore: &Gitignore, path: &Path, is_dir: bool, depth: usize, root: Option<&Path>) -> Option<bool> {
+                let mut is_dir = is_dir;
+
+                for ancestor in path.ancestors().take(depth) {
+                    if Some(ancestor) == root || ancestor.parent().is_none() {
+                        break;
+                    }
+
+                    match gitignore.matched(ancestor, is_dir) {
+                        Match::Ignore(_) => return Some(true),
+                        Match::Whitelist(_) => return Some(false),
+                        Match::None => {}
+                    }
+                    is_dir = true;
+                }
+
+                None
+            }
+
+            /// Expects an absolute path, `depth` levels below the root of the walk
+            pub fn is_ignored(&self, path: &Path, is_dir: bool, depth: usize) -> bool {
+                if path.file_name().is_some_and(|name| name == ".git") {
+                    return true;
+                }
+
+                let mut directories = self.directories.lock().unwrap();
+
+                for directory in path.ancestors().skip(1) {
+                    let gitignore = directories.entry(directory.to_path_buf())
+                        .or_insert_with(|| IgnoreFilter::load(directory));
+
+                    if let Some(ignored) = IgnoreFilter::matched(gitignore, path, is_dir, depth, Some(directory)) {
+                        return ignored;
+                    }
+
+                    // Ignore files don't reach past the root of a repository
+                    if directory.join(".git").exists() {
+                        break;
+                    }
+                }
+
+                std::iter::once(&self.global).chain(self.extra.iter())
+                    .find_map(|gitignore| IgnoreFilter::matched(gitignore, path, is_dir, depth, None))
+                    .unwrap_or(false)
+            }
+        }
+    }
+
+    pub trait ContentScorer: std::fmt::Debug + Send + Sync {
+        fn score(&self, content: &str, target: &str) -> f32;
+        fn get_name(&self) -> String;
+
+        /// Filters reject a file when they score below 1.0, scorers only rank it
+        fn is_filter(&self) -> bool {
+            false
+        }
+
+        /// Expected share of files a filter lets through, used to order runs before any have
+        /// been measured
+        fn pass_rate(&self) -> f32 {
+            0.5
+        }
+
+        /// Called once the run is built, for scorers which can't rely on lowercased targets
+        fn set_insensitive(&mut self, _insensitive: bool) {
+        }
+
+        /// A scorer for content fed in chunks, `None` when the whole content is needed
+        fn stream(&self, _target: &str) -> Option<Box<dyn StreamScorer + '_>> {
+            None
+        }
+
+        /// Where in `content` the scorer finds what it looks for, to point matches out. Empty
+        /// for scorers that look for something being absent.
+        fn find(&self, _content: &str, _target: &str) -> Vec<std::ops::Range<usize>> {
+            Vec::new()
+        }
+    }
+
+    /// Non-overlapping occurrences of a literal, as `str::matches` counts them
+    fn find_literal(content: &str, target: &str) -> Vec<std::ops::Range<usize>> {
+        if target.is_empty() {
+            return Vec::new();
+        }
+        content.match_indices(target).map(|(start, found)| start..start + found.len()).collect()
+    }
+
+    /// Scores content that arrives a chunk at a time
+    pub trait StreamScorer {
+        /// Takes the next chunk, true once no further content can change the score
+        fn feed(&mut self, chunk: &str) -> bool;
+
+        /// The score of everything fed so far
+        fn finish(&mut self) -> f32;
+    }
+
+    /// Non-overlapping occurrences of a literal, counted as `str::matches` counts them in the
+    /// whole content. Only the tail a match could still start in is kept between chunks.
+    struct LiteralStream {
+        target: String,
+        window: String,
+        count: usize,
+        first_only: bool,
+        score: fn(usize) -> f32
+    }
+
+    impl LiteralStream {
+        fn new(target: &str, first_only: bool, score: fn(usize) -> f32) -> Box<LiteralStream> {
+            Box::new(LiteralStream { target: String::from(target), window: String::new(), count: 0, first_only, score })
+        }
+    }
+
+    impl StreamScorer for LiteralStream {
+        fn feed(&mut self, chunk: &str) -> bool {
+            self.window.push_str(chunk);
+
+            let mut end = 0;
+            for (start, m) in self.window.match_indices(self.target.as_str()) {
+                self.count += 1;
+                if self.first_only {
+                    return true;
+                }
+                end = start + m.len();
+            }
+
+            let mut keep = self.window.len().saturating_sub(self.target.len().saturating_sub(1)).max(end);
+            while !self.window.is_char_boundary(keep) {
+                keep -= 1;
+            }
+            self.window.drain(..keep);
+            false
+        }
+
+        fn finish(&mut self) -> f32 {
+            (self.score)(self.count)
+        }
+    }
+
+    /// Lines longer than this are cut, so regex matches spanning the cut are missed
+    const MAX_LINE: usize = 1024 * 1024;
+
+    /// Regex matches over whole lines: chunks are cut after their last line break. Only used
+    /// for patterns `is_line_local` accepts, which can't tell the cuts from the whole content.
+    struct RegexStream<'a> {
+        regex: &'a regex::Regex,
+        window: String,
+        count: usize,
+        first_only: bool,
+        score: fn(usize) -> f32
+    }
+
+    impl RegexStream<'_> {
+        fn new(regex: &regex::Regex, first_only: bool, score: fn(usize) -> f32) -> Box<RegexStream<'_>> {
+            Box::new(RegexStream { regex, window: String::new(), count: 0, first_only, score })
+        }
+
+        fn search(&mut self, end: usize) -> bool {
+            self.count += self.regex.find_iter(&self.window[..end]).count();
+            self.window.drain(..end);
+            self.first_only && self.count > 0
+        }
+    }
+
+    impl StreamScorer for RegexStream<'_> {
+        fn feed(&mut self, chunk: &str) -> bool {
+            self.window.push_str(chunk);
+
+            match self.window.rfind('\n') {
+                Some(end) => self.search(end + 1),
+                None if self.window.len() > MAX_LINE => self.search(self.window.len()),
+                None => false
+            }
+        }
+
+        fn finish(&mut self) -> f32 {
+            self.search(self.window.len());
+            (self.score)(self.count)
+        }
+    }
+    pub trait ContentFilter: std::fmt::Debug {
+        fn filter(&self, content: &str, target: &str) -> bool;
+    }
+
+    #[derive(Debug)]
+    pub struct Is {
+    }
+    impl ContentFilter for Is {
+        fn filter(&self, content: &str, target: &str) -> bool {
+            target.eq(content)
+        }
+    }
+    impl ContentScorer for Is {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("Is")
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+        fn pass_rate(&self) -> f32 {
+            0.1
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Not {
+    }
+    impl ContentFilter for Not {
+        fn filter(&self, content: &str, target: &str) -> bool {
+            !target.eq(content)
+        }
+    }
+    impl ContentScorer for Not {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("Not")
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+        fn pass_rate(&self) -> f32 {
+            0.9
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Has {
+    }
+    impl ContentFilter for Has {
+        fn filter(&self, content: &str, target: &str) -> bool {
+            content.contains(target)
+        }
+    }
+    impl ContentScorer for Has {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("Has")
+        }
+        fn find(&self, content: &str, target: &str) -> Vec<std::ops::Range<usize>> {
+            find_literal(content, target)
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+        fn stream(&self, target: &str) -> Option<Box<dyn StreamScorer + '_>> {
+            Some(LiteralStream::new(target, true, |count| if count > 0 {1.0} else {0.0}))
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Hasnt {
+    }
+    impl ContentFilter for Hasnt {
+        fn filter(&self, content: &str, target: &str) -> bool {
+            !content.contains(target)
+        }
+    }
+    impl ContentScorer for Hasnt {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("Hasnt")
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+        fn pass_rate(&self) -> f32 {
+            0.9
+        }
+        fn stream(&self, target: &str) -> Option<Box<dyn StreamScorer + '_>> {
+            Some(LiteralStream::new(target, true, |count| if count == 0 {1.0} else {0.0}))
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct More {
+    }
+    impl ContentScorer for More {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            let mut score = 1.0;
+
+            for _m in content.matches(target) {
+                score += 1.0;
+            }
+
+            score
+        }
+        fn get_name(&self) -> String {
+            String::from("More")
+        }
+        fn find(&self, content: &str, target: &str) -> Vec<std::ops::Range<usize>> {
+            find_literal(content, target)
+        }
+        fn stream(&self, target: &str) -> Option<Box<dyn StreamScorer + '_>> {
+            Some(LiteralStream::new(target, false, |count| 1.0 + count as f32))
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Less {
+    }
+    impl ContentScorer for Less {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            let mut score = 0.0;
+
+            for _m in content.matches(target) {
+                score -= 1.0;
+            }
+
+            score
+        }
+        fn get_name(&self) -> String {
+            String::from("Less")
+        }
+        fn stream(&self, target: &str) -> Option<Box<dyn StreamScorer + '_>> {
+            Some(LiteralStream::new(target, false, |count| -(count as f32)))
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Matches {
+        regex: regex::Regex,
+        line_local: bool
+    }
+    impl Matches {
+        pub fn new(pattern: &str) -> Result<Matches, String> {
+            Ok(Matches{ regex: build_regex(pattern, false)?, line_local: is_line_local(pattern) })
+        }
+    }
+    impl ContentFilter for Matches {
+        fn filter(&self, content: &str, _target: &str) -> bool {
+            self.regex.is_match(content)
+        }
+    }
+    impl ContentScorer for Matches {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("Matches")
+        }
+        fn find(&self, content: &str, _target: &str) -> Vec<std::ops::Range<usize>> {
+            self.regex.find_iter(content).map(|m| m.range()).collect()
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+        fn set_insensitive(&mut self, insensitive: bool) {
+            self.regex = build_regex(self.regex.as_str(), insensitive).expect("pattern was already validated");
+        }
+        fn stream(&self, _target: &str) -> Option<Box<dyn StreamScorer + '_>> {
+            self.line_local.then(|| RegexStream::new(&self.regex, true, |count| if count > 0 {1.0} else {0.0}) as Box<dyn StreamScorer>)
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct NotMatches {
+        regex: regex::Regex,
+        line_local: bool
+    }
+    impl NotMatches {
+        pub fn new(pattern: &str) -> Result<NotMatches, String> {
+            Ok(NotMatches{ regex: build_regex(pattern, false)?, line_local: is_line_local(pattern) })
+        }
+    }
+    impl ContentFilter for NotMatches {
+        fn filter(&self, content: &str, _target: &str) -> bool {
+            !self.regex.is_match(content)
+        }
+    }
+    impl ContentScorer for NotMatches {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("NotMatches")
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+        fn pass_rate(&self) -> f32 {
+            0.9
+        }
+        fn set_insensitive(&mut self, insensitive: bool) {
+            self.regex = build_regex(self.regex.as_str(), insensitive).expect("pattern was already validated");
+        }
+        fn stream(&self, _target: &str) -> Option<Box<dyn StreamScorer + '_>> {
+            self.line_local.then(|| RegexStream::new(&self.regex, true, |count| if count == 0 {1.0} else {0.0}) as Box<dyn StreamScorer>)
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct MoreRe {
+        regex: regex::Regex,
+        line_local: bool
+    }
+    impl MoreRe {
+        pub fn new(pattern: &str) -> Result<MoreRe, String> {
+            Ok(MoreRe{ regex: build_regex(pattern, false)?, line_local: is_line_local(pattern) })
+        }
+    }
+    impl ContentScorer for MoreRe {
+        fn score(&self, content: &str, _target: &str) -> f32 {
+            1.0 + self.regex.find_iter(content).count() as f32
+        }
+        fn get_name(&self) -> String {
+            String::from("MoreRe")
+        }
+        fn find(&self, content: &str, _target: &str) -> Vec<std::ops::Range<usize>> {
+            self.regex.find_iter(content).map(|m| m.range()).collect()
+        }
+        fn set_insensitive(&mut self, insensitive: bool) {
+            self.regex = build_regex(self.regex.as_str(), insensitive).expect("pattern was already validated");
+        }
+        fn stream(&self, _target: &str) -> Option<Box<dyn StreamScorer + '_>> {
+            self.line_local.then(|| RegexStream::new(&self.regex, false, |count| 1.0 + count as f32) as Box<dyn StreamScorer>)
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct LessRe {
+        regex: regex::Regex,
+        line_local: bool
+    }
+    impl LessRe {
+        pub fn new(pattern: &str) -> Result<LessRe, String> {
+            Ok(LessRe{ regex: build_regex(pattern, false)?, line_local: is_line_local(pattern) })
+        }
+    }
+    impl ContentScorer for LessRe {
+        fn score(&self, content: &str, _target: &str) -> f32 {
+            -(self.regex.find_iter(content).count() as f32)
+        }
+        fn get_name(&self) -> String {
+            String::from("LessRe")
+        }
+        fn set_insensitive(&mut self, insensitive: bool) {
+            self.regex = build_regex(self.regex.as_str(), insensitive).expect("pattern was already validated");
+        }
+        fn stream(&self, _target: &str) -> Option<Box<dyn StreamScorer + '_>> {
+            self.line_local.then(|| RegexStream::new(&self.regex, false, |count| -(count as f32)) as Box<dyn StreamScorer>)
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Gt {
+        bound: f64
+    }
+    impl Gt {
+        pub fn new(target: &str) -> Result<Gt, String> {
+            Ok(Gt{ bound: parse_target(target)? })
+        }
+    }
+    impl ContentFilter for Gt {
+        fn filter(&self, content: &str, _target: &str) -> bool {
+            match parse_number(content) {
+                Some(value) => value > self.bound,
+                None => false
+            }
+        }
+    }
+    impl ContentScorer for Gt {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("Gt")
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Lt {
+        bound: f64
+    }
+    impl Lt {
+        pub fn new(target: &str) -> Result<Lt, String> {
+            Ok(Lt{ bound: parse_target(target)? })
+        }
+    }
+    impl ContentFilter for Lt {
+        fn filter(&self, content: &str, _target: &str) -> bool {
+            match parse_number(content) {
+                Some(value) => value < self.bound,
+                None => false
+            }
+        }
+    }
+    impl ContentScorer for Lt {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("Lt")
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Ge {
+        bound: f64
+    }
+    impl Ge {
+        pub fn new(target: &str) -> Result<Ge, String> {
+            Ok(Ge{ bound: parse_target(target)? })
+        }
+    }
+    impl ContentFilter for Ge {
+        fn filter(&self, content: &str, _target: &str) -> bool {
+            match parse_number(content) {
+                Some(value) => value >= self.bound,
+                None => false
+            }
+        }
+    }
+    impl ContentScorer for Ge {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("Ge")
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Le {
+        bound: f64
+    }
+    impl Le {
+        pub fn new(target: &str) -> Result<Le, String> {
+            Ok(Le{ bound: parse_target(target)? })
+        }
+    }
+    impl ContentFilter for Le {
+        fn filter(&self, content: &str, _target: &str) -> bool {
+            match parse_number(content) {
+                Some(value) => value <= self.bound,
+                None => false
+            }
+        }
+    }
+    impl ContentScorer for Le {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("Le")
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Between {
+        low: f64,
+        high: f64
+    }
+    impl Between {
+        pub fn new(target: &str) -> Result<Between, String> {
+            let (low, high) = parse_range(target)?;
+            Ok(Between{ low, high })
+        }
+    }
+    impl ContentFilter for Between {
+        fn filter(&self, content: &str, _target: &str) -> bool {
+            match parse_number(content) {
+                Some(value) => self.low <= value && value <= self.high,
+                None => false
+            }
+        }
+    }
+    impl ContentScorer for Between {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("Between")
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Newer {
+        bound: DateTime<Local>
+    }
+    impl Newer {
+        pub fn new(target: &str) -> Result<Newer, String> {
+            Ok(Newer{ bound: parse_date_target(target)? })
+        }
+    }
+    impl ContentFilter for Newer {
+        fn filter(&self, content: &str, _target: &str) -> bool {
+            match parse_content_datetime(content) {
+                Some(time) => time > self.bound,
+                None => false
+            }
+        }
+    }
+    impl ContentScorer for Newer {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("Newer")
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Older {
+        bound: DateTime<Local>
+    }
+    impl Older {
+        pub fn new(target: &str) -> Result<Older, String> {
+            Ok(Older{ bound: parse_date_target(target)? })
+        }
+    }
+    impl ContentFilter for Older {
+        fn filter(&self, content: &str, _target: &str) -> bool {
+            match parse_content_datetime(content) {
+                Some(time) => time < self.bound,
+                None => false
+            }
+        }
+    }
+    impl ContentScorer for Older {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("Older")
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct BetweenDates {
+        low: DateTime<Local>,
+        high: DateTime<Local>
+    }
+    impl BetweenDates {
+        pub fn new(target: &str) -> Result<BetweenDates, String> {
+            match target.split_once("..") {
+                Some((a, b)) => {
+                    // Relative ranges read naturally either way round, e.g. `2d..1d`
+                    let (a, b) = (parse_date_target(a)?, parse_date_target(b)?);
+                    Ok(BetweenDates{ low: a.min(b), high: a.max(b) })
+                },
+                None => Err(format!("invalid date range '{}', expected FROM..TO", target))
+            }
+        }
+    }
+    impl ContentFilter for BetweenDates {
+        fn filter(&self, content: &str, _target: &str) -> bool {
+            match parse_content_datetime(content) {
+                Some(time) => self.low <= time && time <= self.high,
+                None => false
+            }
+        }
+    }
+    impl ContentScorer for BetweenDates {
+        fn score(&self, content: &str, target: &str) -> f32 {
+            if self.filter(content, target) {1.0} else {0.0}
+        }
+        fn get_name(&self) -> String {
+            String::from("BetweenDates")
+        }
+        fn is_filter(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Pass {
+
+    }
+    impl ContentScorer for Pass {
+        fn score(&self, _content: &str, _target: &str) -> f32 {
+            1.0
+        }
+        fn get_name(&self) -> String {
+            String::from("Pass")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scorers::{self, ContentScorer};
+
+    /// Scores content fed a few bytes at a time when the scorer streams, whole otherwise
+    fn streamed_score(scorer: &dyn ContentScorer, content: &str, target: &str) -> f32 {
+        match scorer.stream(target) {
+            Some(mut stream) => {
+                for chunk in content.as_bytes().chunks(3) {
+                    if stream.feed(std::str::from_utf8(chunk).unwrap()) {
+                        break;
+                    }
+                }
+                stream.finish()
+            },
+            None => scorer.score(content, target)
+        }
+    }
+
+    #[test]
+    fn streamed_regex_scores_match_whole_content() {
+        let contents = ["abc\nfoo", "foo\nabc", "ab\ncd\nfoo bar\n", "x\ny\nfoo", "foo", "", "foofoo\nfoo\n"];
+        let patterns = ["^foo", "\\Afoo", "foo$", "(?m)^foo", "(?m)foo$", "\\bfoo\\b", "c\\nf", "c\\sf", "fo+", "x*", "\\z"];
+
+        for name in ["matches", "not-matches", "more-re", "less-re"] {
+            for pattern in patterns {
+                let scorer = scorers::parse(name, pattern).unwrap().unwrap();
+                for content in contents {
+                    assert_eq!(streamed_score(scorer.as_ref(), content, pattern), scorer.score(content, pattern),
+                        "--{} '{}' on {:?}", name, pattern, content);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn date_filters_only_take_absolute_times_from_content() {
+        let scorer = scorers::parse("older", "2030-01-01").unwrap().unwrap();
+        assert_eq!(scorer.score("2026-10-17 12:00:00", "2030-01-01"), 1.0);
+        assert_eq!(scorer.score("2026-10-17T12:00:00+00:00", "2030-01-01"), 1.0);
+        for content in ["2026-10-17", "2026-10-17 12:00", "2026-10-17T12:00:00"] {
+            assert_eq!(scorer.score(content, "2030-01-01"), 1.0, "{:?}", content);
+        }
+        for content in ["5m", "1700000000", "@1700000000", "today", "now"] {
+            assert_eq!(scorer.score(content, "2030-01-01"), 0.0, "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn timestamps_need_an_at_sign() {
+        assert_eq!(scorers::parse_datetime("@1700000000").map(|datetime| datetime.timestamp()), Some(1700000000));
+        assert!(scorers::parse_datetime("1700000000").is_none());
+        assert!(scorers::parse("newer", "20240101").is_err());
+    }
+
+    #[test]
+    fn plain_patterns_still_stream() {
+        let scorer = scorers::parse("more-re", "fo+").unwrap().unwrap();
+        assert!(scorer.stream("fo+").is_some());
+
+        let scorer = scorers::parse("matches", "^foo").unwrap().unwrap();
+        assert!(scorer.stream("^foo").is_none());
     }
 }